@@ -1,24 +1,43 @@
 //! provides function attribute macros for rust-crowbar crate
 
 extern crate proc_macro;
+extern crate proc_macro2;
 #[macro_use]
 extern crate quote;
 extern crate syn;
 
+use std::env;
+
 use proc_macro::TokenStream;
-use syn::{parse, ItemFn, ReturnType};
+use proc_macro2::{Group, Span, TokenStream as TokenStream2, TokenTree};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::token::{Comma, Underscore};
+use syn::{
+    Error, FnArg, GenericParam, Ident, Item, Lit, Meta, NestedMeta, Result, ReturnType, Type,
+};
 
 /// Implements the `lambdafn` attribute.
 ///
 /// This attribute is used to export a Rust function into an
-/// AWS triggerable Lambda function. In lambda you can refer to these by path with
-/// `liblambda.{fn_name}`
+/// AWS triggerable Lambda function. List the attributed functions of a crate in crowbar's
+/// `module!` macro, and in lambda you can refer to them by path with `liblambda.{fn_name}`; a
+/// function that isn't listed fails to compile. Use
+/// `#[lambdafn(name = "...")]` to export a function under a different name, and
+/// `#[lambdafn(module = "kappa")]` to check that it's exported from `libkappa`.
+///
+/// The first argument is the event, which can be a `crowbar::Value` or any type that implements
+/// serde's `Deserialize`. It can be followed by the context, either as a `crowbar::LambdaContext`
+/// or borrowed as a `&crowbar::LambdaContext`, and then by a reference to state of any type that
+/// implements `Default` and `Send`. The state is created with the module and kept for as long as
+/// the Lambda container stays warm.
+///
+/// `async fn` handlers are run to completion on the thread that invoked them.
 ///
 /// # Examples
 ///
 /// ```rust,ignore
 /// #[macro_use] extern crate crowbar;
-/// #[macro_use] extern crate cpython;
 ///
 /// #[lambdafn]
 /// fn example(
@@ -27,46 +46,552 @@ use syn::{parse, ItemFn, ReturnType};
 /// ) -> crowbar::LambdaResult {
 ///     Ok(event)
 /// }
+///
+/// #[lambdafn(name = "count")]
+/// fn count_messages(event: SqsEvent, ctx: &LambdaContext, seen: &Cell<usize>) -> LambdaResult<usize> {
+///     seen.set(seen.get() + event.records.len());
+///     Ok(seen.get())
+/// }
+///
+/// module!(example, count_messages);
 /// ```
 #[proc_macro_attribute]
 pub fn lambdafn(args: TokenStream, input: TokenStream) -> TokenStream {
     attr_impl(args, input)
 }
 
+/// How the handler takes the context, with the context type as the handler names it.
+enum Context<'a> {
+    Owned(&'a Type),
+    Borrowed(&'a Type),
+}
+
 // implementation. should expect the following
 // * verify function type
-// * input args are (event, context)
+// * input args are (event, [context | &context], [&state])
 // * has a return type
-fn attr_impl(_: TokenStream, input: TokenStream) -> TokenStream {
-    let target: ItemFn = match parse(input.clone()) {
-        Ok(f) => f,
-        _ => {
-            panic!("the 'lambdafn' attribute can only be used on functions");
-            // https://doc.rust-lang.org/proc_macro/struct.Span.html#method.error
-            // use span diagnotics when this becomes stable
+fn attr_impl(args: TokenStream, input: TokenStream) -> TokenStream {
+    match expand(args.into(), input.into()) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(args: TokenStream2, input: TokenStream2) -> Result<TokenStream2> {
+    let (name, module) = handler_args(args)?;
+    let target = match syn::parse2(input)? {
+        Item::Fn(f) => f,
+        item => {
+            return Err(Error::new_spanned(
+                item,
+                "the 'lambdafn' attribute can only be used on functions",
+            ))
         }
     };
-    if target.decl.inputs.len() != 2 {
-        panic!(
-            "the 'lambdafn' attribute requires a function with two arguments. expecting {}(_: crowbar::Value, _: crowbar::LambdaContext) -> crowbar::LambdaResult", target.ident
-            );
-            // https://doc.rust-lang.org/proc_macro/struct.Span.html#method.error
-            // use span diagnotics when it becomes stable
-    }
-    match target.decl.output {
-        ReturnType::Default => {
-            // https://doc.rust-lang.org/proc_macro/struct.Span.html#method.error
-            // use span diagnotics when it becomes stable
-            panic!("the 'lambdafn' attribute requires a function that returns a value. expecting {}(_: crowbar::Value, _: crowbar::LambdaContext) -> crowbar::LambdaResult", target.ident);
-        },
-        _ => ()
+    let expecting = format!(
+        "expecting `fn {}(event: E[, context: LambdaContext | &LambdaContext][, state: &S]) -> \
+         LambdaResult<O>`, where `E` is `Value` or implements `Deserialize` and `S` implements \
+         `Default` and `Send`",
+        target.ident
+    );
+
+    for param in &target.decl.generics.params {
+        match *param {
+            GenericParam::Lifetime(_) => (),
+            _ => {
+                return Err(Error::new_spanned(
+                    param,
+                    "the 'lambdafn' attribute requires a function without type parameters",
+                ))
+            }
+        }
+    }
+
+    let mut types = Vec::new();
+    for arg in &target.decl.inputs {
+        match *arg {
+            FnArg::Captured(ref arg) => types.push(&arg.ty),
+            _ => {
+                return Err(Error::new_spanned(
+                    arg,
+                    format!(
+                        "the 'lambdafn' attribute requires a function with typed arguments. {}",
+                        expecting
+                    ),
+                ))
+            }
+        }
     }
+    let mut types = types.into_iter();
+
+    let event = match types.next() {
+        Some(ty) => event_type(ty)?,
+        None => {
+            return Err(Error::new(
+                target.decl.paren_token.span,
+                format!(
+                    "the 'lambdafn' attribute requires a function with an event argument. {}",
+                    expecting
+                ),
+            ))
+        }
+    };
+    let mut context = None;
+    let mut state = None;
+    for ty in types {
+        match *ty {
+            _ if state.is_some() => {
+                return Err(Error::new_spanned(
+                    ty,
+                    "the 'lambdafn' attribute requires state to be the last argument",
+                ))
+            }
+            Type::Path(_) if is_context(ty) => {
+                if context.is_some() {
+                    return Err(Error::new_spanned(ty, "the context can only be taken once"));
+                }
+                context = Some(Context::Owned(ty))
+            }
+            Type::Reference(ref r) if is_context(&r.elem) => {
+                if r.mutability.is_some() {
+                    return Err(Error::new_spanned(
+                        ty,
+                        "the context can be taken by value or by shared reference, not `&mut`",
+                    ));
+                }
+                if context.is_some() {
+                    return Err(Error::new_spanned(ty, "the context can only be taken once"));
+                }
+                context = Some(Context::Borrowed(&r.elem))
+            }
+            Type::Reference(ref r) => {
+                if r.mutability.is_some() {
+                    return Err(Error::new_spanned(
+                        ty,
+                        "state is shared between invocations and must be taken by shared \
+                         reference; use a `Cell` or `RefCell` to change it",
+                    ));
+                }
+                state = Some(&r.elem)
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    ty,
+                    "expected `LambdaContext`, `&LambdaContext` or a reference to state",
+                ))
+            }
+        }
+    }
+
+    let output = match target.decl.output {
+        ReturnType::Type(_, ref ty) => ty,
+        ReturnType::Default => {
+            return Err(Error::new(
+                target.decl.paren_token.span,
+                format!(
+                    "the 'lambdafn' attribute requires a function that returns a value. {}",
+                    expecting
+                ),
+            ))
+        }
+    };
     let target_ident = target.ident.clone();
-    let target_name = target_ident.to_string();
-    let expanded = quote! {
+    let target_vis = target.vis.clone();
+    let target_name = name.unwrap_or_else(|| target_ident.to_string());
+    let target_module = match module {
+        Some(module) => {
+            let module = format!("lib{}", module);
+            quote!(::std::option::Option::Some(#module))
+        }
+        None => quote!(::std::option::Option::None),
+    };
+
+    // The generated code can't name crowbar, which may be renamed by the crate using it. The
+    // context is taken as whatever type the handler names, or any type when it doesn't take it,
+    // and `module!` passes in crowbar's `block_on` to run `async fn` handlers.
+    let lifetimes = target.decl.generics.params.iter();
+    let mut generics = vec![quote!(__B)];
+    let mut args = vec![quote!(__event)];
+    let context = match context {
+        Some(Context::Owned(ty)) => {
+            args.push(quote!(__context));
+            quote!(#ty)
+        }
+        Some(Context::Borrowed(ty)) => {
+            args.push(quote!(&__context));
+            quote!(#ty)
+        }
+        None => {
+            generics.push(quote!(__C));
+            quote!(__C)
+        }
+    };
+    if state.is_some() {
+        args.push(quote!(&self.__state));
+    }
+    let mut call = quote!(#target_ident(#(#args),*));
+    let block_on = if target.asyncness.is_some() {
+        call = quote!(__block_on(::std::boxed::Box::pin(#call)));
+        quote!(__block_on)
+    } else {
+        quote!(_)
+    };
+    let (fields, init) = match state {
+        Some(state) => (
+            quote!({ __state: #state }),
+            quote!({ __state: ::std::default::Default::default() }),
+        ),
+        None => (quote!({}), quote!({})),
+    };
+
+    // `module!` marks the handlers it exports with an inherent `__EXPORTED` constant, which takes
+    // precedence over the trait's. Handlers it doesn't list get the trait's, which fails to
+    // compile, since they would otherwise be silently left out of the module.
+    let not_exported = format!(
+        "`{}` is a #[lambdafn] handler but isn't exported; list it in crowbar's `module!`",
+        target_ident
+    );
+
+    // The handler is a value of a type with the same name as the function, which doesn't clash
+    // with it because types and functions live in different namespaces. `module!` creates it once
+    // with `handler` and calls it for every invocation, so it owns the state.
+    Ok(quote! {
         #target
 
-        lambda!(#target_name => #target_ident);
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #target_vis struct #target_ident #fields
+
+        impl #target_ident {
+            #[doc(hidden)]
+            pub const NAME: &'static str = #target_name;
+
+            #[doc(hidden)]
+            pub const MODULE: ::std::option::Option<&'static str> = #target_module;
+
+            #[doc(hidden)]
+            pub fn handler() -> #target_ident {
+                #target_ident #init
+            }
+
+            #[doc(hidden)]
+            pub fn call<#(#lifetimes,)* #(#generics),*>(
+                &mut self,
+                __event: #event,
+                __context: #context,
+                #block_on: __B,
+            ) -> #output
+            where
+                __B: FnOnce(
+                    // `'_` goes first so that 2015 edition crates don't read `dyn ::std` as a path.
+                    ::std::pin::Pin<
+                        ::std::boxed::Box<dyn '_ + ::std::future::Future<Output = #output>>,
+                    >,
+                ) -> #output,
+            {
+                #call
+            }
+        }
+
+        const _: () = {
+            trait NotExported {
+                const __EXPORTED: () = panic!(#not_exported);
+            }
+            impl<T> NotExported for T {}
+            #target_ident::__EXPORTED
+        };
+    })
+}
+
+/// Parses the attribute's arguments, returning the handler name given with `name = "..."` and
+/// the module given with `module = "..."`.
+///
+/// The module is checked against the name Cargo builds the library with here, and against the
+/// module the handler is exported from by `module!`.
+fn handler_args(args: TokenStream2) -> Result<(Option<String>, Option<Ident>)> {
+    let args = Punctuated::<NestedMeta, Comma>::parse_terminated.parse2(args)?;
+    let mut name = None;
+    let mut module = None;
+    for arg in &args {
+        match *arg {
+            NestedMeta::Meta(Meta::NameValue(ref arg)) if arg.ident == "name" => {
+                if name.is_some() {
+                    return Err(Error::new_spanned(arg, "the handler name can only be set once"));
+                }
+                let value = match arg.lit {
+                    Lit::Str(ref value) => value.value(),
+                    _ => return Err(Error::new_spanned(&arg.lit, "expected a string")),
+                };
+                if value.is_empty() || value.contains('.') {
+                    return Err(Error::new_spanned(
+                        &arg.lit,
+                        "the handler name must be non-empty and can't contain `.`",
+                    ));
+                }
+                name = Some(value);
+            }
+            NestedMeta::Meta(Meta::NameValue(ref arg)) if arg.ident == "module" => {
+                if module.is_some() {
+                    return Err(Error::new_spanned(arg, "the module can only be set once"));
+                }
+                let value = match arg.lit {
+                    Lit::Str(ref value) => value.parse::<Ident>().map_err(|_| {
+                        Error::new_spanned(value, "the module name must be an identifier")
+                    })?,
+                    _ => return Err(Error::new_spanned(&arg.lit, "expected a string")),
+                };
+                check_module(&value).map_err(|e| Error::new_spanned(&arg.lit, e))?;
+                module = Some(value);
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    arg,
+                    "unknown argument, expecting `name = \"...\"` or `module = \"...\"`",
+                ))
+            }
+        }
+    }
+    Ok((name, module))
+}
+
+/// Checks that the event is a type that can be deserialized: `Value` or an owned, named type.
+fn event_type(ty: &Type) -> Result<&Type> {
+    match *ty {
+        _ if is_context(ty) => Err(Error::new_spanned(
+            ty,
+            "the event must be the first argument, followed by the context",
+        )),
+        Type::Reference(ref r) if is_context(&r.elem) => Err(Error::new_spanned(
+            ty,
+            "the event must be the first argument, followed by the context",
+        )),
+        Type::Reference(_) => Err(Error::new_spanned(
+            ty,
+            "the event must be taken by value, such as `crowbar::Value` or a type that \
+             implements `Deserialize`",
+        )),
+        Type::Path(_) | Type::Tuple(_) | Type::Array(_) => Ok(ty),
+        _ => Err(Error::new_spanned(
+            ty,
+            "expected `crowbar::Value` or a type that implements `Deserialize`",
+        )),
+    }
+}
+
+/// Whether `ty` is a path to `LambdaContext`, such as `crowbar::LambdaContext<'a>`.
+fn is_context(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) => match path.path.segments.iter().last() {
+            Some(segment) => segment.ident == "LambdaContext",
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Implements the module initialization of `lambda!`.
+///
+/// Takes the path to crowbar, the module, either `_` for the name of the library Cargo is
+/// building, a name such as `kappa` for `libkappa`, or the `(libkappa, initlibkappa,
+/// PyInit_libkappa)` symbols, then the initializer passed on to `py_module_initializer!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __py_module_initializer(input: TokenStream) -> TokenStream {
+    match module_initializer(input.into()) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn module_initializer(input: TokenStream2) -> Result<TokenStream2> {
+    let input: ModuleInitializer = syn::parse2(input)?;
+    let symbols = match input.module {
+        Module::Symbols(ref symbols) => {
+            check_symbols(symbols)?;
+            symbols.stream()
+        }
+        Module::Named(ref name) => {
+            check_module(name).map_err(|e| Error::new_spanned(name, e))?;
+            module_symbols(&input.module)
+        }
+        Module::Derived => module_symbols(&input.module),
+    };
+    let krate = input.krate;
+    let init = input.init;
+    Ok(quote! {
+        #krate::__private::py_module_initializer!(#symbols, #init);
+    })
+}
+
+/// The name of the Python module: the first of its symbols.
+fn module_name(module: &Module) -> String {
+    match *module {
+        Module::Derived => format!(
+            "lib{}",
+            env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| "lambda".to_string())
+        ),
+        Module::Named(ref name) => format!("lib{}", name),
+        Module::Symbols(ref symbols) => match symbols.stream().into_iter().next() {
+            Some(module) => module.to_string(),
+            None => String::new(),
+        },
+    }
+}
+
+/// The module, Python 2 and Python 3 initializer symbols for a module that isn't given in full.
+fn module_symbols(module: &Module) -> TokenStream2 {
+    let name = module_name(module);
+    let symbol = |prefix: &str| Ident::new(&format!("{}{}", prefix, name), Span::call_site());
+    let (module, py2, py3) = (symbol(""), symbol("init"), symbol("PyInit_"));
+    quote!(#module, #py2, #py3)
+}
+
+/// Checks that symbols given in full as `(libkappa, initlibkappa, PyInit_libkappa)` agree with
+/// each other and, like a named module, with the name Cargo builds the library with.
+fn check_symbols(symbols: &Group) -> Result<()> {
+    let parsed = Punctuated::<Ident, Comma>::parse_terminated
+        .parse2(symbols.stream())
+        .ok()
+        .filter(|parsed| parsed.len() == 3);
+    let parsed = match parsed {
+        Some(parsed) => parsed.into_iter().collect::<Vec<_>>(),
+        None => {
+            return Err(Error::new_spanned(
+                symbols,
+                "expected the module and its initializers, such as \
+                 `(libkappa, initlibkappa, PyInit_libkappa)`",
+            ))
+        }
     };
-    expanded.into()
-}
\ No newline at end of file
+    let module = parsed[0].to_string();
+    for (symbol, prefix) in parsed[1..].iter().zip(&["init", "PyInit_"]) {
+        let expected = format!("{}{}", prefix, module);
+        if *symbol != expected {
+            return Err(Error::new_spanned(
+                symbol,
+                format!(
+                    "the initializer of the module `{}` must be named `{}`",
+                    module, expected
+                ),
+            ));
+        }
+    }
+    match module.strip_prefix("lib") {
+        Some(name) if !name.is_empty() => check_module(&Ident::new(name, parsed[0].span()))
+            .map_err(|e| Error::new_spanned(&parsed[0], e)),
+        _ => Err(Error::new_spanned(
+            &parsed[0],
+            format!(
+                "the module must be named after the library, such as `lib{}`",
+                module
+            ),
+        )),
+    }
+}
+
+/// Implements the check `module!` makes that handlers declared with `#[lambdafn(module = "...")]`
+/// are exported from that module.
+///
+/// Takes the path to crowbar, the module as `lambda!` takes it, then the paths to the handlers.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __check_module(input: TokenStream) -> TokenStream {
+    match check_handler_modules(input.into()) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn check_handler_modules(input: TokenStream2) -> Result<TokenStream2> {
+    let input: HandlerModules = syn::parse2(input)?;
+    let krate = input.krate;
+    let module = module_name(&input.module);
+    let checks = input.handlers.iter().map(|handler| {
+        let message = format!(
+            "`{}` is declared with #[lambdafn(module = \"...\")] for a module other than `{}`, \
+             which `module!` exports it from",
+            quote!(#handler).to_string().replace(' ', ""),
+            module
+        );
+        quote! {
+            const _: () = if !#krate::__private::in_module(#handler::MODULE, #module) {
+                panic!(#message)
+            };
+        }
+    });
+    Ok(quote!(#(#checks)*))
+}
+
+/// Checks that `name` is the name Cargo builds the library with, the `name` of its `[lib]`
+/// section, so that Python finds the module in the library.
+fn check_module(name: &Ident) -> ::std::result::Result<(), String> {
+    match env::var("CARGO_CRATE_NAME") {
+        Ok(ref lib) if name != lib => Err(format!(
+            "the module is named `lib{}`, but Cargo builds the library as `lib{}`; \
+             set `name = \"{}\"` in the `[lib]` section of Cargo.toml",
+            name, lib, name
+        )),
+        _ => Ok(()),
+    }
+}
+
+struct HandlerModules {
+    krate: TokenTree,
+    module: Module,
+    handlers: Punctuated<syn::Path, Comma>,
+}
+
+struct ModuleInitializer {
+    krate: TokenTree,
+    module: Module,
+    init: TokenStream2,
+}
+
+enum Module {
+    Derived,
+    Named(Ident),
+    Symbols(Group),
+}
+
+impl Parse for Module {
+    fn parse(input: ParseStream) -> Result<Module> {
+        if input.peek(Underscore) {
+            input.parse::<Underscore>()?;
+            Ok(Module::Derived)
+        } else if input.peek(Ident) {
+            Ok(Module::Named(input.parse()?))
+        } else {
+            Ok(Module::Symbols(input.parse()?))
+        }
+    }
+}
+
+impl Parse for HandlerModules {
+    fn parse(input: ParseStream) -> Result<HandlerModules> {
+        // `$crate`, which isn't an identifier syn can parse.
+        let krate = input.parse()?;
+        input.parse::<Comma>()?;
+        let module = input.parse()?;
+        input.parse::<Comma>()?;
+        Ok(HandlerModules {
+            krate,
+            module,
+            handlers: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+impl Parse for ModuleInitializer {
+    fn parse(input: ParseStream) -> Result<ModuleInitializer> {
+        // `$crate`, which isn't an identifier syn can parse.
+        let krate = input.parse()?;
+        input.parse::<Comma>()?;
+        let module = input.parse()?;
+        input.parse::<Comma>()?;
+        Ok(ModuleInitializer {
+            krate,
+            module,
+            init: input.parse()?,
+        })
+    }
+}
@@ -1,5 +1,3 @@
-#[macro_use]
-extern crate cpython;
 #[macro_use(lambda)]
 extern crate crowbar;
 
@@ -1,40 +1,37 @@
-#[macro_use]
-extern crate cpython;
 #[macro_use(lambda)]
 extern crate crowbar;
-extern crate rusoto_core;
-extern crate rusoto_ec2;
+#[macro_use]
+extern crate serde_derive;
 
+use crowbar::py;
 use crowbar::{LambdaContext, LambdaResult, Value};
-use rusoto_core::{default_tls_client, DefaultCredentialsProvider, Region};
-use rusoto_ec2::{DescribeRegionsRequest, Ec2, Ec2Client};
-use std::default::Default;
-use std::env;
-use std::str::FromStr;
 
-fn list_regions(_: Value, _: LambdaContext) -> LambdaResult {
-    let provider = DefaultCredentialsProvider::new()?;
-    let region_str = env::var("AWS_DEFAULT_REGION")?;
-    let client = Ec2Client::new(
-        default_tls_client()?,
-        provider,
-        Region::from_str(&region_str)?,
-    );
-    let input: DescribeRegionsRequest = Default::default();
+// boto3 is available in the Lambda execution environment, so there's no AWS SDK to compile or
+// link against.
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeRegions {
+    regions: Vec<Region>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Region {
+    region_name: String,
+}
 
-    match client.describe_regions(&input)?.regions {
-        Some(regions) => {
-            let mut v = vec![];
-            for region in regions {
-                match region.region_name {
-                    Some(s) => v.push(Value::String(s)),
-                    _ => {}
-                }
-            }
-            Ok(Value::Array(v))
-        }
-        None => Ok(Value::Array(vec![])),
-    }
+fn list_regions(_: Value, context: LambdaContext) -> LambdaResult<Vec<String>> {
+    let py = context.python();
+    let client = py::import(py, "boto3")?.call_method(py, "client", ("ec2",), ())?;
+    let response: DescribeRegions = client
+        .call_method(py, "describe_regions", (), ())?
+        .extract(py)?;
+    Ok(response
+        .regions
+        .into_iter()
+        .map(|region| region.region_name)
+        .collect())
 }
 
 lambda!(list_regions);
@@ -0,0 +1,567 @@
+//! CloudFormation custom resources.
+//!
+//! CloudFormation invokes the Lambda function backing a [custom resource]
+//! (https://docs.aws.amazon.com/AWSCloudFormation/latest/UserGuide/template-custom-resources-lambda.html)
+//! when the resource is created, updated or deleted, and then waits for a response document to be
+//! uploaded to a pre-signed S3 URL. If no response arrives, the stack hangs for up to an hour.
+//!
+//! Implement `CustomResource` for your resource and wrap it with `custom_resource`. crowbar sends
+//! a `SUCCESS` response when your implementation returns `Ok`, and a `FAILED` response when it
+//! returns an error, panics, or is about to run out of time.
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use crowbar::LambdaResult;
+//! use crowbar::cfn::{custom_resource, CreateRequest, CustomResource, DeleteRequest,
+//!                    ResourceOutput, UpdateRequest};
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename_all = "PascalCase")]
+//! struct Properties {
+//!     name: String,
+//! }
+//!
+//! struct Greeting;
+//!
+//! impl CustomResource for Greeting {
+//!     type Properties = Properties;
+//!
+//!     fn create(&mut self, request: &CreateRequest<Properties>) -> LambdaResult<ResourceOutput> {
+//!         let name = &request.resource_properties.name;
+//!         Ok(ResourceOutput::new()
+//!             .with_physical_resource_id(name.clone())
+//!             .with_data("Greeting", format!("hello, {}", name)))
+//!     }
+//!
+//!     fn update(&mut self, request: &UpdateRequest<Properties>) -> LambdaResult<ResourceOutput> {
+//!         let name = &request.resource_properties.name;
+//!         Ok(ResourceOutput::new()
+//!             .with_physical_resource_id(name.clone())
+//!             .with_data("Greeting", format!("hello again, {}", name)))
+//!     }
+//!
+//!     fn delete(&mut self, _: &DeleteRequest<Properties>) -> LambdaResult<ResourceOutput> {
+//!         Ok(ResourceOutput::new())
+//!     }
+//! }
+//!
+//! lambda!(|event, context| custom_resource(Greeting).handle(event, context));
+//! # }
+//! ```
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use cpython::{NoArgs, ObjectProtocol, PyBytes, PyDict, PyResult, Python};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use {LambdaContext, LambdaResult};
+
+/// A request from CloudFormation to a custom resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "RequestType")]
+pub enum CfnRequest<P = Value> {
+    Create(CreateRequest<P>),
+    Update(UpdateRequest<P>),
+    Delete(DeleteRequest<P>),
+}
+
+impl<P> CfnRequest<P> {
+    /// Fields common to every request type.
+    pub fn info(&self) -> &RequestInfo {
+        match *self {
+            CfnRequest::Create(ref request) => &request.info,
+            CfnRequest::Update(ref request) => &request.info,
+            CfnRequest::Delete(ref request) => &request.info,
+        }
+    }
+
+    /// The physical ID of the resource, which is only known once it has been created.
+    pub fn physical_resource_id(&self) -> Option<&str> {
+        match *self {
+            CfnRequest::Create(_) => None,
+            CfnRequest::Update(ref request) => Some(&request.physical_resource_id),
+            CfnRequest::Delete(ref request) => Some(&request.physical_resource_id),
+        }
+    }
+}
+
+/// Fields common to every custom resource request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RequestInfo {
+    /// The ARN of the Lambda function backing the resource.
+    pub service_token: String,
+    /// The pre-signed S3 URL the response is uploaded to.
+    #[serde(rename = "ResponseURL")]
+    pub response_url: String,
+    pub stack_id: String,
+    pub request_id: String,
+    /// The resource type from the template, such as `Custom::Greeting`.
+    pub resource_type: String,
+    pub logical_resource_id: String,
+}
+
+/// A request to create a resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreateRequest<P = Value> {
+    #[serde(flatten)]
+    pub info: RequestInfo,
+    pub resource_properties: P,
+}
+
+/// A request to update a resource whose properties changed.
+///
+/// Returning a different physical resource ID replaces the resource: CloudFormation sends a
+/// `DeleteRequest` for the old physical ID once the stack update completes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateRequest<P = Value> {
+    #[serde(flatten)]
+    pub info: RequestInfo,
+    pub physical_resource_id: String,
+    pub resource_properties: P,
+    pub old_resource_properties: P,
+}
+
+/// A request to delete a resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeleteRequest<P = Value> {
+    #[serde(flatten)]
+    pub info: RequestInfo,
+    pub physical_resource_id: String,
+    pub resource_properties: P,
+}
+
+/// The response document uploaded to the response URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CfnResponse {
+    pub status: ResponseStatus,
+    /// Shown in the CloudFormation console; required for `FAILED` responses.
+    pub reason: String,
+    pub physical_resource_id: String,
+    pub stack_id: String,
+    pub request_id: String,
+    pub logical_resource_id: String,
+    /// Masks `data` when it is retrieved with `Fn::GetAtt`.
+    pub no_echo: bool,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub data: HashMap<String, Value>,
+}
+
+/// Whether a request succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResponseStatus {
+    #[serde(rename = "SUCCESS")]
+    Success,
+    #[serde(rename = "FAILED")]
+    Failed,
+}
+
+/// The result of successfully handling a request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceOutput {
+    /// The physical ID of the resource. If not set, crowbar keeps the resource's current physical
+    /// ID, or uses the function's log stream name for new resources.
+    pub physical_resource_id: Option<String>,
+    /// Attributes available to the template with `Fn::GetAtt`.
+    pub data: HashMap<String, Value>,
+    /// Masks `data` when it is retrieved with `Fn::GetAtt`.
+    pub no_echo: bool,
+}
+
+impl ResourceOutput {
+    pub fn new() -> ResourceOutput {
+        ResourceOutput::default()
+    }
+
+    pub fn with_physical_resource_id<S>(mut self, id: S) -> ResourceOutput
+    where
+        S: Into<String>,
+    {
+        self.physical_resource_id = Some(id.into());
+        self
+    }
+
+    pub fn with_data<K, V>(mut self, key: K, value: V) -> ResourceOutput
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.data.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_no_echo(mut self, no_echo: bool) -> ResourceOutput {
+        self.no_echo = no_echo;
+        self
+    }
+}
+
+/// A custom resource implementation.
+///
+/// Each method runs on its own thread, so that crowbar can respond to CloudFormation if it runs
+/// out of time.
+pub trait CustomResource {
+    /// The type `ResourceProperties` deserializes into. Note that CloudFormation passes every
+    /// property as a string, and adds the `ServiceToken` property.
+    type Properties: DeserializeOwned;
+
+    fn create(&mut self, request: &CreateRequest<Self::Properties>) -> LambdaResult<ResourceOutput>;
+
+    fn update(&mut self, request: &UpdateRequest<Self::Properties>) -> LambdaResult<ResourceOutput>;
+
+    fn delete(&mut self, request: &DeleteRequest<Self::Properties>) -> LambdaResult<ResourceOutput>;
+}
+
+/// Delivers a response document to CloudFormation.
+///
+/// Closures with the same signature as `send` implement this trait.
+pub trait ResponseSender {
+    fn send(&mut self, url: &str, response: &CfnResponse) -> LambdaResult<()>;
+}
+
+impl<F> ResponseSender for F
+where
+    F: FnMut(&str, &CfnResponse) -> LambdaResult<()>,
+{
+    fn send(&mut self, url: &str, response: &CfnResponse) -> LambdaResult<()> {
+        self(url, response)
+    }
+}
+
+/// Sends responses with an HTTP `PUT` using Python's `urllib.request`, which is available in the
+/// Lambda execution environment. Requires Python 3.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UrllibSender;
+
+impl ResponseSender for UrllibSender {
+    fn send(&mut self, url: &str, response: &CfnResponse) -> LambdaResult<()> {
+        let body = serde_json::to_vec(response).map_err(|e| e.to_string())?;
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        put(py, url, &body)
+            .map_err(|mut e| format!("failed to send response: {}", e.instance(py)))?;
+        Ok(())
+    }
+}
+
+fn put(py: Python, url: &str, body: &[u8]) -> PyResult<()> {
+    let urllib = py.import("urllib.request")?;
+    // the pre-signed URL is signed without a content type
+    let headers = PyDict::new(py);
+    headers.set_item(py, "Content-Type", "")?;
+    let kwargs = PyDict::new(py);
+    kwargs.set_item(py, "data", PyBytes::new(py, body))?;
+    kwargs.set_item(py, "headers", headers)?;
+    kwargs.set_item(py, "method", "PUT")?;
+    let request = urllib.call(py, "Request", (url,), Some(&kwargs))?;
+    urllib
+        .call(py, "urlopen", (request,), None)?
+        .call_method(py, "close", NoArgs, None)?;
+    Ok(())
+}
+
+/// Wraps a `CustomResource` into a handler that always responds to CloudFormation.
+pub fn custom_resource<R>(resource: R) -> CustomResourceHandler<R>
+where
+    R: CustomResource,
+{
+    CustomResourceHandler {
+        resource: resource,
+        sender: UrllibSender,
+        timeout_margin: Duration::from_secs(2),
+    }
+}
+
+/// Handler for a custom resource, created by `custom_resource`.
+pub struct CustomResourceHandler<R, S = UrllibSender> {
+    resource: R,
+    sender: S,
+    timeout_margin: Duration,
+}
+
+impl<R, S> CustomResourceHandler<R, S>
+where
+    R: CustomResource + Send + 'static,
+    R::Properties: Send + 'static,
+    S: ResponseSender,
+{
+    /// Replaces the `UrllibSender` used to deliver responses.
+    pub fn with_sender<T>(self, sender: T) -> CustomResourceHandler<R, T>
+    where
+        T: ResponseSender,
+    {
+        CustomResourceHandler {
+            resource: self.resource,
+            sender: sender,
+            timeout_margin: self.timeout_margin,
+        }
+    }
+
+    /// Sets how long before the function times out crowbar gives up on the resource and responds
+    /// with `FAILED`. Defaults to two seconds.
+    pub fn with_timeout_margin(mut self, margin: Duration) -> Self {
+        self.timeout_margin = margin;
+        self
+    }
+
+    /// Handles a request and sends the response.
+    ///
+    /// This only returns an error if `event` isn't a custom resource request, or if the response
+    /// can't be sent. Errors from the `CustomResource` are reported to CloudFormation instead.
+    pub fn handle(mut self, event: Value, context: LambdaContext) -> LambdaResult<()> {
+        let info: RequestInfo = serde_json::from_value(event.clone())
+            .map_err(|e| format!("not a CloudFormation custom resource request: {}", e))?;
+        let current_id = event["PhysicalResourceId"]
+            .as_str()
+            .unwrap_or_else(|| context.log_stream_name())
+            .to_string();
+
+        let timeout = context
+            .get_remaining_time_in_millis()
+            .ok()
+            .map(|remaining| Duration::from_millis(remaining).checked_sub(self.timeout_margin))
+            .map(|timeout| timeout.unwrap_or_default());
+        let result = match serde_json::from_value::<CfnRequest<R::Properties>>(event) {
+            Ok(request) => run(self.resource, request, timeout),
+            Err(e) => Err(format!("invalid request: {}", e)),
+        };
+
+        let response = match result {
+            Ok(output) => CfnResponse {
+                status: ResponseStatus::Success,
+                reason: format!(
+                    "See the details in CloudWatch Log Stream: {}",
+                    context.log_stream_name()
+                ),
+                physical_resource_id: output.physical_resource_id.unwrap_or(current_id),
+                stack_id: info.stack_id,
+                request_id: info.request_id,
+                logical_resource_id: info.logical_resource_id,
+                no_echo: output.no_echo,
+                data: output.data,
+            },
+            Err(reason) => {
+                error!("custom resource failed: {}", reason);
+                CfnResponse {
+                    status: ResponseStatus::Failed,
+                    reason: reason,
+                    physical_resource_id: current_id,
+                    stack_id: info.stack_id,
+                    request_id: info.request_id,
+                    logical_resource_id: info.logical_resource_id,
+                    no_echo: false,
+                    data: HashMap::new(),
+                }
+            }
+        };
+        self.sender.send(&info.response_url, &response)
+    }
+}
+
+/// Runs the request on another thread, waiting at most `timeout` for it with the GIL released.
+fn run<R>(
+    mut resource: R,
+    request: CfnRequest<R::Properties>,
+    timeout: Option<Duration>,
+) -> Result<ResourceOutput, String>
+where
+    R: CustomResource + Send + 'static,
+    R::Properties: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            match request {
+                CfnRequest::Create(ref request) => resource.create(request),
+                CfnRequest::Update(ref request) => resource.update(request),
+                CfnRequest::Delete(ref request) => resource.delete(request),
+            }.map_err(|e| e.to_string())
+        }));
+        let _ = tx.send(result.unwrap_or_else(|payload| Err(panic_message(payload))));
+    });
+
+    let gil = Python::acquire_gil();
+    gil.python().allow_threads(move || match timeout {
+        Some(timeout) => rx.recv_timeout(timeout)
+            .unwrap_or_else(|_| Err("timed out waiting for the custom resource".to_string())),
+        None => rx.recv()
+            .unwrap_or_else(|_| Err("custom resource exited without a result".to_string())),
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown error".to_string(),
+        },
+    };
+    format!("custom resource panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+    use serde_json::{self, Value};
+    use LambdaContext;
+
+    struct Echo;
+
+    impl CustomResource for Echo {
+        type Properties = HashMap<String, String>;
+
+        fn create(&mut self, request: &CreateRequest<Self::Properties>) -> LambdaResult<ResourceOutput> {
+            match request.resource_properties.get("Behavior").map(String::as_str) {
+                Some("fail") => Err("failed on purpose".into()),
+                Some("panic") => panic!("panicked on purpose"),
+                Some("sleep") => {
+                    thread::sleep(Duration::from_secs(5));
+                    Ok(ResourceOutput::new())
+                }
+                _ => Ok(ResourceOutput::new()
+                    .with_physical_resource_id("echo")
+                    .with_data("Name", request.resource_properties["Name"].clone())),
+            }
+        }
+
+        fn update(&mut self, _: &UpdateRequest<Self::Properties>) -> LambdaResult<ResourceOutput> {
+            Ok(ResourceOutput::new())
+        }
+
+        fn delete(&mut self, _: &DeleteRequest<Self::Properties>) -> LambdaResult<ResourceOutput> {
+            Ok(ResourceOutput::new())
+        }
+    }
+
+    fn create(url: &str, behavior: &str) -> Value {
+        json!({
+            "RequestType": "Create",
+            "ServiceToken": "arn:aws:lambda:us-east-1:123456789012:function:echo",
+            "ResponseURL": url,
+            "StackId": "arn:aws:cloudformation:us-east-1:123456789012:stack/test/guid",
+            "RequestId": "request",
+            "ResourceType": "Custom::Echo",
+            "LogicalResourceId": "Echo",
+            "ResourceProperties": {"Name": "crowbar", "Behavior": behavior}
+        })
+    }
+
+    fn respond(event: Value, context: LambdaContext) -> CfnResponse {
+        let mut responses = vec![];
+        custom_resource(Echo)
+            .with_timeout_margin(Duration::from_millis(0))
+            .with_sender(|_: &str, response: &CfnResponse| {
+                responses.push(response.clone());
+                Ok(())
+            })
+            .handle(event, context)
+            .unwrap();
+        assert_eq!(responses.len(), 1);
+        responses.remove(0)
+    }
+
+    #[test]
+    fn success() {
+        let response = respond(create("https://example.com", "succeed"), LambdaContext::fake());
+        assert_eq!(response.status, ResponseStatus::Success);
+        assert_eq!(response.physical_resource_id, "echo");
+        assert_eq!(response.data["Name"], "crowbar");
+        assert_eq!(response.request_id, "request");
+    }
+
+    #[test]
+    fn failure() {
+        let context = LambdaContext::fake().with_log_stream_name("stream");
+        let response = respond(create("https://example.com", "fail"), context);
+        assert_eq!(response.status, ResponseStatus::Failed);
+        assert_eq!(response.reason, "failed on purpose");
+        assert_eq!(response.physical_resource_id, "stream");
+    }
+
+    #[test]
+    fn panic() {
+        let response = respond(create("https://example.com", "panic"), LambdaContext::fake());
+        assert_eq!(response.status, ResponseStatus::Failed);
+        assert_eq!(response.reason, "custom resource panicked: panicked on purpose");
+    }
+
+    #[test]
+    fn timeout() {
+        let context = LambdaContext::fake().with_remaining_time_in_millis(100);
+        let response = respond(create("https://example.com", "sleep"), context);
+        assert_eq!(response.status, ResponseStatus::Failed);
+        assert_eq!(response.reason, "timed out waiting for the custom resource");
+    }
+
+    #[test]
+    fn invalid_request() {
+        let mut event = create("https://example.com", "succeed");
+        event["RequestType"] = json!("Replace");
+        let response = respond(event, LambdaContext::fake());
+        assert_eq!(response.status, ResponseStatus::Failed);
+        assert!(response.reason.starts_with("invalid request"));
+
+        let result = custom_resource(Echo)
+            .with_sender(|_: &str, _: &CfnResponse| Ok(()))
+            .handle(json!({"hello": "world"}), LambdaContext::fake());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn urllib_sender() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/response?signature=x", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let header = header.to_lowercase();
+                if let Some(length) = header.strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (request_line, body)
+        });
+
+        custom_resource(Echo)
+            .handle(create(&url, "succeed"), LambdaContext::fake())
+            .unwrap();
+
+        let (request_line, body) = server.join().unwrap();
+        assert_eq!(request_line.trim(), "PUT /response?signature=x HTTP/1.1");
+        let response: CfnResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.status, ResponseStatus::Success);
+        assert_eq!(response.logical_resource_id, "Echo");
+    }
+}
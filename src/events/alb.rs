@@ -0,0 +1,382 @@
+//! Application Load Balancer target events.
+//!
+//! An [ALB target group]
+//! (https://docs.aws.amazon.com/elasticloadbalancing/latest/application/lambda-functions.html)
+//! sends headers and query string parameters either with one value per name, or with every value
+//! when multi-value headers are enabled on the target group, and expects the response in the same
+//! format. `AlbRequest` accepts both, and responses created with `AlbRequest::response` use the
+//! format the request arrived in:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::alb::AlbRequest;
+//!
+//! lambda!(|request: AlbRequest, _context| {
+//!     let name = request.query("name").unwrap_or("world");
+//!     Ok(request.response(200)
+//!         .with_header("content-type", "text/plain")
+//!         .with_body(format!("hello, {}", name)))
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use base64;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+/// A request forwarded by a load balancer.
+///
+/// Headers and query string parameters are stored with all their values regardless of the target
+/// group setting. Header names are lowercase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlbRequest {
+    pub request_context: AlbRequestContext,
+    pub http_method: String,
+    pub path: String,
+    pub query_string_parameters: HashMap<String, Vec<String>>,
+    pub headers: HashMap<String, Vec<String>>,
+    /// The body, base64-encoded if `is_base64_encoded` is set. See `body_bytes`.
+    pub body: String,
+    pub is_base64_encoded: bool,
+    /// Whether the target group has multi-value headers enabled.
+    pub multi_value: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlbRequestContext {
+    pub elb: ElbContext,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElbContext {
+    pub target_group_arn: String,
+}
+
+impl AlbRequest {
+    /// The first value of a header. `name` is case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        first(&self.headers, &name.to_lowercase())
+    }
+
+    /// The first value of a query string parameter.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        first(&self.query_string_parameters, name)
+    }
+
+    /// The decoded body.
+    pub fn body_bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        if self.is_base64_encoded {
+            base64::decode(&self.body)
+        } else {
+            Ok(self.body.clone().into_bytes())
+        }
+    }
+
+    /// Creates a response in the header format of this request.
+    pub fn response(&self, status_code: u16) -> AlbResponse {
+        AlbResponse {
+            multi_value: self.multi_value,
+            ..AlbResponse::new(status_code)
+        }
+    }
+}
+
+fn first<'a>(map: &'a HashMap<String, Vec<String>>, name: &str) -> Option<&'a str> {
+    map.get(name)
+        .and_then(|values| values.first())
+        .map(String::as_str)
+}
+
+/// The wire format of `AlbRequest`, which has either the single-value or the multi-value fields.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRequest {
+    request_context: AlbRequestContext,
+    http_method: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    query_string_parameters: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    multi_value_query_string_parameters: Option<HashMap<String, Vec<String>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    headers: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    multi_value_headers: Option<HashMap<String, Vec<String>>>,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    is_base64_encoded: bool,
+}
+
+fn to_multi(map: HashMap<String, String>) -> HashMap<String, Vec<String>> {
+    map.into_iter().map(|(k, v)| (k, vec![v])).collect()
+}
+
+fn to_single(map: &HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    // the last value wins, like it does for ALB
+    map.iter()
+        .filter_map(|(k, v)| v.last().map(|v| (k.clone(), v.clone())))
+        .collect()
+}
+
+impl<'de> Deserialize<'de> for AlbRequest {
+    fn deserialize<D>(deserializer: D) -> Result<AlbRequest, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawRequest::deserialize(deserializer)?;
+        let multi_value = raw.multi_value_headers.is_some();
+        Ok(AlbRequest {
+            request_context: raw.request_context,
+            http_method: raw.http_method,
+            path: raw.path,
+            query_string_parameters: match raw.multi_value_query_string_parameters {
+                Some(params) => params,
+                None => to_multi(raw.query_string_parameters.unwrap_or_default()),
+            },
+            headers: match raw.multi_value_headers {
+                Some(headers) => headers,
+                None => to_multi(raw.headers.unwrap_or_default()),
+            },
+            body: raw.body.unwrap_or_default(),
+            is_base64_encoded: raw.is_base64_encoded,
+            multi_value: multi_value,
+        })
+    }
+}
+
+impl Serialize for AlbRequest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (query, multi_query, headers, multi_headers) = if self.multi_value {
+            (
+                None,
+                Some(self.query_string_parameters.clone()),
+                None,
+                Some(self.headers.clone()),
+            )
+        } else {
+            (
+                Some(to_single(&self.query_string_parameters)),
+                None,
+                Some(to_single(&self.headers)),
+                None,
+            )
+        };
+        RawRequest {
+            request_context: self.request_context.clone(),
+            http_method: self.http_method.clone(),
+            path: self.path.clone(),
+            query_string_parameters: query,
+            multi_value_query_string_parameters: multi_query,
+            headers: headers,
+            multi_value_headers: multi_headers,
+            body: Some(self.body.clone()),
+            is_base64_encoded: self.is_base64_encoded,
+        }.serialize(serializer)
+    }
+}
+
+/// The response a Lambda function returns to a load balancer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlbResponse {
+    pub status_code: u16,
+    /// The status line text, such as `200 OK`. Derived from `status_code` if not set.
+    pub status_description: Option<String>,
+    pub headers: HashMap<String, Vec<String>>,
+    pub body: String,
+    pub is_base64_encoded: bool,
+    /// Whether to send `multiValueHeaders` instead of `headers`; this must match the target group
+    /// setting.
+    pub multi_value: bool,
+}
+
+impl AlbResponse {
+    /// Creates an empty single-value response. Prefer `AlbRequest::response`, which matches the
+    /// header format of the request.
+    pub fn new(status_code: u16) -> AlbResponse {
+        AlbResponse {
+            status_code: status_code,
+            status_description: None,
+            headers: HashMap::new(),
+            body: String::new(),
+            is_base64_encoded: false,
+            multi_value: false,
+        }
+    }
+
+    /// Adds a header value. In single-value mode only the last value of each header is sent.
+    pub fn with_header<K, V>(mut self, name: K, value: V) -> AlbResponse
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.entry(name.into()).or_default().push(value.into());
+        self
+    }
+
+    pub fn with_status_description<S>(mut self, description: S) -> AlbResponse
+    where
+        S: Into<String>,
+    {
+        self.status_description = Some(description.into());
+        self
+    }
+
+    pub fn with_body<S>(mut self, body: S) -> AlbResponse
+    where
+        S: Into<String>,
+    {
+        self.body = body.into();
+        self.is_base64_encoded = false;
+        self
+    }
+
+    /// Sets a binary body, which is sent base64-encoded.
+    pub fn with_binary_body(mut self, body: &[u8]) -> AlbResponse {
+        self.body = base64::encode(body);
+        self.is_base64_encoded = true;
+        self
+    }
+
+    fn status_line(&self) -> String {
+        let reason = match self.status_code {
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            204 => "No Content",
+            301 => "Moved Permanently",
+            302 => "Found",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            429 => "Too Many Requests",
+            500 => "Internal Server Error",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "",
+        };
+        format!("{} {}", self.status_code, reason).trim().to_string()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RawResponse<'a> {
+    status_code: u16,
+    status_description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    multi_value_headers: Option<&'a HashMap<String, Vec<String>>>,
+    body: &'a str,
+    is_base64_encoded: bool,
+}
+
+impl Serialize for AlbResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        RawResponse {
+            status_code: self.status_code,
+            status_description: self.status_description
+                .clone()
+                .unwrap_or_else(|| self.status_line()),
+            headers: if self.multi_value {
+                None
+            } else {
+                Some(to_single(&self.headers))
+            },
+            multi_value_headers: if self.multi_value {
+                Some(&self.headers)
+            } else {
+                None
+            },
+            body: &self.body,
+            is_base64_encoded: self.is_base64_encoded,
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn single_value() {
+        let request: AlbRequest = serde_json::from_value(json!({
+            "requestContext": {"elb": {"targetGroupArn": "arn:aws:elasticloadbalancing:tg"}},
+            "httpMethod": "GET",
+            "path": "/",
+            "queryStringParameters": {"name": "crowbar"},
+            "headers": {"host": "example.com"},
+            "body": "",
+            "isBase64Encoded": false
+        })).unwrap();
+        assert!(!request.multi_value);
+        assert_eq!(request.query("name"), Some("crowbar"));
+        assert_eq!(request.header("Host"), Some("example.com"));
+
+        let response = request
+            .response(200)
+            .with_header("set-cookie", "a=1")
+            .with_header("set-cookie", "b=2")
+            .with_body("hello");
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "statusCode": 200,
+                "statusDescription": "200 OK",
+                "headers": {"set-cookie": "b=2"},
+                "body": "hello",
+                "isBase64Encoded": false
+            })
+        );
+    }
+
+    #[test]
+    fn multi_value() {
+        let request: AlbRequest = serde_json::from_value(json!({
+            "requestContext": {"elb": {"targetGroupArn": "arn:aws:elasticloadbalancing:tg"}},
+            "httpMethod": "POST",
+            "path": "/upload",
+            "multiValueQueryStringParameters": {"tag": ["a", "b"]},
+            "multiValueHeaders": {"accept": ["text/html", "*/*"]},
+            "body": "aGVsbG8=",
+            "isBase64Encoded": true
+        })).unwrap();
+        assert!(request.multi_value);
+        assert_eq!(request.query_string_parameters["tag"], vec!["a", "b"]);
+        assert_eq!(request.header("accept"), Some("text/html"));
+        assert_eq!(request.body_bytes().unwrap(), b"hello");
+
+        let response = request
+            .response(418)
+            .with_header("set-cookie", "a=1")
+            .with_header("set-cookie", "b=2")
+            .with_binary_body(b"teapot");
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "statusCode": 418,
+                "statusDescription": "418",
+                "multiValueHeaders": {"set-cookie": ["a=1", "b=2"]},
+                "body": "dGVhcG90",
+                "isBase64Encoded": true
+            })
+        );
+    }
+}
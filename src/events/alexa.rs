@@ -0,0 +1,383 @@
+//! Alexa Skills Kit requests and responses.
+//!
+//! Alexa sends a [request](https://developer.amazon.com/docs/custom-skills/request-and-response-json-reference.html)
+//! to the skill's function whenever the user launches it, speaks an intent or ends the session,
+//! and speaks the response back to the user:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::alexa::{AlexaRequest, AlexaResponse, Request};
+//!
+//! lambda!(|event: AlexaRequest, _context| {
+//!     Ok(match event.request {
+//!         Request::LaunchRequest(_) => AlexaResponse::speak("Welcome. Who should I greet?")
+//!             .reprompt("Who should I greet?")
+//!             .end_session(false),
+//!         Request::IntentRequest(ref request) if request.intent.name == "HelloIntent" => {
+//!             let name = request.intent.slot_value("Name").unwrap_or("world");
+//!             AlexaResponse::speak(format!("Hello, {}!", name))
+//!                 .simple_card("Hello", format!("Hello, {}!", name))
+//!         }
+//!         _ => AlexaResponse::new(),
+//!     })
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+/// A request from Alexa to a custom skill.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlexaRequest {
+    pub version: String,
+    /// Not included for requests that aren't part of a session, such as `AudioPlayer` requests.
+    #[serde(default)]
+    pub session: Option<Session>,
+    /// The state of the device and the Alexa service.
+    #[serde(default)]
+    pub context: Value,
+    pub request: Request,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// Whether this is the first request of the session.
+    pub new: bool,
+    pub session_id: String,
+    pub application: Application,
+    /// Attributes set by the previous response in the session.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub attributes: HashMap<String, Value>,
+    pub user: User,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Application {
+    pub application_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub user_id: String,
+    /// Set when the user has linked their account to the skill.
+    #[serde(default)]
+    pub access_token: Option<String>,
+}
+
+/// The request types a custom skill receives.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Request {
+    LaunchRequest(LaunchRequest),
+    IntentRequest(IntentRequest),
+    SessionEndedRequest(SessionEndedRequest),
+    /// Any other request type, such as the `AudioPlayer` and `Display` interface requests.
+    #[serde(other)]
+    Other,
+}
+
+/// Sent when the user starts the skill without an intent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchRequest {
+    pub request_id: String,
+    pub timestamp: String,
+    pub locale: String,
+}
+
+/// Sent when the user speaks an intent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntentRequest {
+    pub request_id: String,
+    pub timestamp: String,
+    pub locale: String,
+    /// `STARTED`, `IN_PROGRESS` or `COMPLETED` for skills with a dialog model.
+    #[serde(default)]
+    pub dialog_state: Option<String>,
+    pub intent: Intent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Intent {
+    pub name: String,
+    /// `NONE`, `CONFIRMED` or `DENIED`.
+    #[serde(default)]
+    pub confirmation_status: Option<String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub slots: HashMap<String, Slot>,
+}
+
+impl Intent {
+    /// The value of a slot, if the user provided one.
+    pub fn slot_value(&self, name: &str) -> Option<&str> {
+        self.slots
+            .get(name)
+            .and_then(|slot| slot.value.as_ref())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Slot {
+    pub name: String,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub confirmation_status: Option<String>,
+}
+
+/// Sent when the session ends for any reason other than the skill ending it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEndedRequest {
+    pub request_id: String,
+    pub timestamp: String,
+    pub locale: String,
+    /// `USER_INITIATED`, `ERROR` or `EXCEEDED_MAX_REPROMPTS`.
+    pub reason: String,
+    #[serde(default)]
+    pub error: Option<Value>,
+}
+
+/// The response a skill returns to Alexa.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlexaResponse {
+    pub version: String,
+    /// Attributes Alexa sends back with the next request in the session.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub session_attributes: HashMap<String, Value>,
+    pub response: ResponseBody,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseBody {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_speech: Option<OutputSpeech>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card: Option<Card>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reprompt: Option<Reprompt>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub should_end_session: Option<bool>,
+}
+
+/// Speech for Alexa to say, as plain text or SSML.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum OutputSpeech {
+    PlainText { text: String },
+    #[serde(rename = "SSML")]
+    Ssml { ssml: String },
+}
+
+/// Speech for Alexa to say if the user doesn't respond.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reprompt {
+    pub output_speech: OutputSpeech,
+}
+
+/// A card displayed in the Alexa app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Card {
+    Simple { title: String, content: String },
+    Standard {
+        title: String,
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<CardImage>,
+    },
+    /// Asks the user to link their account to the skill.
+    LinkAccount,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardImage {
+    pub small_image_url: String,
+    pub large_image_url: String,
+}
+
+impl AlexaResponse {
+    /// Creates an empty response, which ends the session without saying anything.
+    pub fn new() -> AlexaResponse {
+        AlexaResponse {
+            version: "1.0".to_string(),
+            session_attributes: HashMap::new(),
+            response: ResponseBody::default(),
+        }
+    }
+
+    /// Creates a response that says `text`.
+    pub fn speak<S>(text: S) -> AlexaResponse
+    where
+        S: Into<String>,
+    {
+        let mut response = AlexaResponse::new();
+        response.response.output_speech = Some(OutputSpeech::PlainText { text: text.into() });
+        response
+    }
+
+    /// Creates a response that says the SSML document `ssml`.
+    pub fn speak_ssml<S>(ssml: S) -> AlexaResponse
+    where
+        S: Into<String>,
+    {
+        let mut response = AlexaResponse::new();
+        response.response.output_speech = Some(OutputSpeech::Ssml { ssml: ssml.into() });
+        response
+    }
+
+    /// Says `text` if the user doesn't respond. Only used if the session stays open.
+    pub fn reprompt<S>(mut self, text: S) -> AlexaResponse
+    where
+        S: Into<String>,
+    {
+        self.response.reprompt = Some(Reprompt {
+            output_speech: OutputSpeech::PlainText { text: text.into() },
+        });
+        self
+    }
+
+    /// Says the SSML document `ssml` if the user doesn't respond.
+    pub fn reprompt_ssml<S>(mut self, ssml: S) -> AlexaResponse
+    where
+        S: Into<String>,
+    {
+        self.response.reprompt = Some(Reprompt {
+            output_speech: OutputSpeech::Ssml { ssml: ssml.into() },
+        });
+        self
+    }
+
+    pub fn simple_card<T, C>(mut self, title: T, content: C) -> AlexaResponse
+    where
+        T: Into<String>,
+        C: Into<String>,
+    {
+        self.response.card = Some(Card::Simple {
+            title: title.into(),
+            content: content.into(),
+        });
+        self
+    }
+
+    pub fn standard_card<T, C>(mut self, title: T, text: C, image: Option<CardImage>) -> AlexaResponse
+    where
+        T: Into<String>,
+        C: Into<String>,
+    {
+        self.response.card = Some(Card::Standard {
+            title: title.into(),
+            text: text.into(),
+            image: image,
+        });
+        self
+    }
+
+    pub fn link_account_card(mut self) -> AlexaResponse {
+        self.response.card = Some(Card::LinkAccount);
+        self
+    }
+
+    /// Whether the session ends after this response. Alexa ends it if this isn't set.
+    pub fn end_session(mut self, end: bool) -> AlexaResponse {
+        self.response.should_end_session = Some(end);
+        self
+    }
+
+    pub fn with_session_attribute<K, V>(mut self, key: K, value: V) -> AlexaResponse
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.session_attributes.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl Default for AlexaResponse {
+    fn default() -> AlexaResponse {
+        AlexaResponse::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn intent_request() {
+        let event: AlexaRequest = serde_json::from_value(json!({
+            "version": "1.0",
+            "session": {
+                "new": true,
+                "sessionId": "amzn1.echo-api.session.1",
+                "application": {"applicationId": "amzn1.ask.skill.1"},
+                "attributes": {},
+                "user": {"userId": "amzn1.ask.account.1"}
+            },
+            "context": {},
+            "request": {
+                "type": "IntentRequest",
+                "requestId": "amzn1.echo-api.request.1",
+                "timestamp": "2019-01-02T12:45:07Z",
+                "locale": "en-US",
+                "intent": {
+                    "name": "HelloIntent",
+                    "confirmationStatus": "NONE",
+                    "slots": {"Name": {"name": "Name", "value": "crowbar"}}
+                }
+            }
+        })).unwrap();
+        match event.request {
+            Request::IntentRequest(ref request) => {
+                assert_eq!(request.intent.slot_value("Name"), Some("crowbar"))
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+
+        let other: Request = serde_json::from_value(json!({
+            "type": "AudioPlayer.PlaybackStarted",
+            "requestId": "amzn1.echo-api.request.2"
+        })).unwrap();
+        assert_eq!(other, Request::Other);
+    }
+
+    #[test]
+    fn response() {
+        let response = AlexaResponse::speak("Hello")
+            .reprompt_ssml("<speak>Anyone there?</speak>")
+            .simple_card("Hello", "Hello, crowbar")
+            .end_session(false)
+            .with_session_attribute("greeted", true);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "version": "1.0",
+                "sessionAttributes": {"greeted": true},
+                "response": {
+                    "outputSpeech": {"type": "PlainText", "text": "Hello"},
+                    "card": {"type": "Simple", "title": "Hello", "content": "Hello, crowbar"},
+                    "reprompt": {
+                        "outputSpeech": {"type": "SSML", "ssml": "<speak>Anyone there?</speak>"}
+                    },
+                    "shouldEndSession": false
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Events from any source, for functions with several triggers.
+//!
+//! `AnyEvent` detects which service sent an event from its shape, such as the `eventSource` of its
+//! records, and deserializes it as that service's event type. Events it doesn't recognize, such as
+//! direct invocations, are kept as JSON in the `Unknown` variant.
+
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde_json::{self, Value};
+
+use cfn::CfnRequest;
+use codepipeline::CodePipelineJobEvent;
+use super::alb::AlbRequest;
+use super::authorizer::AuthorizerEvent;
+use super::cloudwatch_logs::CloudWatchLogsEvent;
+use super::codecommit::CodeCommitEvent;
+use super::cognito::CognitoTrigger;
+use super::eventbridge::EventBridgeEvent;
+use super::firehose::FirehoseEvent;
+use super::kafka::KafkaEvent;
+use super::ses::SesEvent;
+use super::sns::SnsEvent;
+use super::sqs::SqsEvent;
+
+/// An event from any of the sources crowbar has types for, for functions with several triggers.
+///
+/// The source is detected from the shape of the event, such as `Records[].eventSource` for SQS and
+/// SNS, `requestContext.elb` for load balancers and `awslogs` for CloudWatch Logs. Events that
+/// don't match any source, including direct invocations, are kept as `Unknown`. So are events
+/// that look like they come from a source but can't be deserialized as its type; crowbar logs a
+/// warning when this happens.
+///
+/// ```rust
+/// # #[macro_use(lambda)] extern crate crowbar;
+/// # fn main() {
+/// use crowbar::events::AnyEvent;
+///
+/// lambda!(|event: AnyEvent, _context| {
+///     let messages: Vec<String> = match event {
+///         AnyEvent::Sqs(event) => event.records.into_iter().map(|r| r.body).collect(),
+///         AnyEvent::Sns(event) => event.records.into_iter().map(|r| r.sns.message).collect(),
+///         AnyEvent::Unknown(value) => vec![value.to_string()],
+///         _ => return Err("unsupported event source".into()),
+///     };
+///     Ok(messages.len())
+/// });
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum AnyEvent {
+    Alb(AlbRequest),
+    Authorizer(AuthorizerEvent),
+    CloudFormation(CfnRequest),
+    CloudWatchLogs(CloudWatchLogsEvent),
+    CodeCommit(CodeCommitEvent),
+    CodePipeline(CodePipelineJobEvent),
+    Cognito(Box<CognitoTrigger>),
+    EventBridge(EventBridgeEvent),
+    Firehose(FirehoseEvent),
+    Kafka(KafkaEvent),
+    Ses(SesEvent),
+    Sns(SnsEvent),
+    Sqs(SqsEvent),
+    Unknown(Value),
+}
+
+impl<'de> Deserialize<'de> for AnyEvent {
+    fn deserialize<D>(deserializer: D) -> Result<AnyEvent, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        Ok(detect(value))
+    }
+}
+
+fn detect(value: Value) -> AnyEvent {
+    fn has(value: &Value, key: &str) -> bool {
+        value.get(key).is_some()
+    }
+
+    let record_source = {
+        let record = &value["Records"][0];
+        record["eventSource"]
+            .as_str()
+            .or_else(|| record["EventSource"].as_str())
+            .map(String::from)
+    };
+    match record_source.as_deref() {
+        Some("aws:codecommit") => return typed(value, AnyEvent::CodeCommit),
+        Some("aws:sqs") => return typed(value, AnyEvent::Sqs),
+        Some("aws:ses") => return typed(value, AnyEvent::Ses),
+        Some("aws:sns") => return typed(value, AnyEvent::Sns),
+        _ => (),
+    }
+
+    let source = value["eventSource"].as_str().map(String::from);
+    match source.as_deref() {
+        Some("aws:kafka") | Some("SelfManagedKafka") => return typed(value, AnyEvent::Kafka),
+        _ => (),
+    }
+
+    if has(&value, "awslogs") {
+        typed(value, AnyEvent::CloudWatchLogs)
+    } else if has(&value, "CodePipeline.job") {
+        typed(value, AnyEvent::CodePipeline)
+    } else if has(&value["requestContext"], "elb") {
+        typed(value, AnyEvent::Alb)
+    } else if has(&value, "methodArn") && has(&value, "type") {
+        typed(value, AnyEvent::Authorizer)
+    } else if has(&value, "deliveryStreamArn") {
+        typed(value, AnyEvent::Firehose)
+    } else if has(&value, "triggerSource") && has(&value, "userPoolId") {
+        typed(value, AnyEvent::Cognito)
+    } else if has(&value, "detail-type") && has(&value, "source") {
+        typed(value, AnyEvent::EventBridge)
+    } else if has(&value, "RequestType") && has(&value, "ResponseURL") {
+        typed(value, AnyEvent::CloudFormation)
+    } else {
+        AnyEvent::Unknown(value)
+    }
+}
+
+fn typed<T, F>(value: Value, variant: F) -> AnyEvent
+where
+    T: DeserializeOwned,
+    F: FnOnce(T) -> AnyEvent,
+{
+    match serde_json::from_value(value.clone()) {
+        Ok(event) => variant(event),
+        Err(e) => {
+            warn!("event looks like a known event source, but failed to deserialize: {}", e);
+            AnyEvent::Unknown(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{self, Value};
+
+    fn detect(value: Value) -> AnyEvent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn records() {
+        let sqs = detect(json!({"Records": [{
+            "messageId": "1", "receiptHandle": "handle", "body": "hello", "md5OfBody": "md5",
+            "eventSource": "aws:sqs", "eventSourceARN": "arn:aws:sqs:us-east-1:123456789012:q",
+            "awsRegion": "us-east-1"
+        }]}));
+        match sqs {
+            AnyEvent::Sqs(ref event) => assert_eq!(event.records[0].body, "hello"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let sns = detect(json!({"Records": [{
+            "EventVersion": "1.0", "EventSubscriptionArn": "arn", "EventSource": "aws:sns",
+            "Sns": {
+                "Type": "Notification", "MessageId": "1", "TopicArn": "arn", "Message": "hello",
+                "Timestamp": "2019-01-02T12:45:07.000Z", "SignatureVersion": "1",
+                "Signature": "sig", "SigningCertUrl": "url", "UnsubscribeUrl": "url"
+            }
+        }]}));
+        match sns {
+            AnyEvent::Sns(ref event) => assert_eq!(event.records[0].sns.message, "hello"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let s3 = json!({"Records": [{"eventSource": "aws:s3"}]});
+        assert_eq!(detect(s3.clone()), AnyEvent::Unknown(s3));
+    }
+
+    #[test]
+    fn shapes() {
+        let alb = detect(json!({
+            "requestContext": {"elb": {"targetGroupArn": "arn"}},
+            "httpMethod": "GET", "path": "/", "headers": {}, "body": "", "isBase64Encoded": false
+        }));
+        match alb {
+            AnyEvent::Alb(ref request) => assert_eq!(request.path, "/"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let scheduled = detect(json!({
+            "version": "0", "id": "1", "detail-type": "Scheduled Event", "source": "aws.events",
+            "account": "123456789012", "time": "2019-01-02T12:45:07Z", "region": "us-east-1",
+            "resources": [], "detail": {}
+        }));
+        match scheduled {
+            AnyEvent::EventBridge(ref event) => assert_eq!(event.source, "aws.events"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let token = detect(json!({"type": "TOKEN", "authorizationToken": "t", "methodArn": "arn"}));
+        match token {
+            AnyEvent::Authorizer(_) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown() {
+        let direct = json!({"hello": "world"});
+        assert_eq!(detect(direct.clone()), AnyEvent::Unknown(direct));
+
+        // looks like a load balancer event, but is missing fields
+        let broken = json!({"requestContext": {"elb": {}}});
+        assert_eq!(detect(broken.clone()), AnyEvent::Unknown(broken));
+
+        // round trips as the original event
+        let unknown = detect(json!([1, 2, 3]));
+        assert_eq!(serde_json::to_value(&unknown).unwrap(), json!([1, 2, 3]));
+    }
+}
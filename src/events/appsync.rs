@@ -0,0 +1,366 @@
+//! AppSync direct Lambda resolver events.
+//!
+//! AppSync invokes a [direct Lambda resolver]
+//! (https://docs.aws.amazon.com/appsync/latest/devguide/resolver-reference-lambda.html) with the
+//! arguments of the field being resolved, the caller's identity and the parent object. The
+//! function returns the value of the field. `AppSyncResolverEvent` takes the types of the
+//! arguments and the parent object as parameters, and `ResolverRouter` calls a different function
+//! for each field so one function can resolve a whole schema. Build the router once and keep it
+//! for every invocation:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use crowbar::{LambdaContext, LambdaResult};
+//! use crowbar::Value;
+//! use crowbar::events::appsync::{AppSyncResolverEvent, ResolverRouter};
+//!
+//! #[derive(Deserialize)]
+//! struct GetPost {
+//!     id: String,
+//! }
+//!
+//! #[derive(Deserialize, Serialize)]
+//! struct Post {
+//!     id: String,
+//!     author: String,
+//! }
+//!
+//! fn get_post(event: AppSyncResolverEvent<GetPost>, _: &LambdaContext) -> LambdaResult<Post> {
+//!     Ok(Post { id: event.arguments.id, author: "crowbar".to_string() })
+//! }
+//!
+//! fn post_author(
+//!     event: AppSyncResolverEvent<Value, Post>,
+//!     _: &LambdaContext,
+//! ) -> LambdaResult<String> {
+//!     Ok(event.source.map(|post| post.author).unwrap_or_default())
+//! }
+//!
+//! lambda!({
+//!     let mut router = ResolverRouter::new()
+//!         .route("Query", "getPost", get_post)
+//!         .route("Post", "author", post_author);
+//!     move |event, context| router.handle(event, context)
+//! });
+//! # }
+//! ```
+//!
+//! When batching is enabled on a resolver, AppSync sends an array of events and expects an array
+//! of results in the same order. `ResolverRouter::handle` accepts both. For a batch, each result
+//! is an object with the value in `data`, or with `errorMessage` and `errorType` if resolving that
+//! field failed, so one failing field doesn't fail the others.
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{self, Value};
+
+use {LambdaContext, LambdaResult};
+
+/// The event a direct Lambda resolver receives, with `arguments` of type `A` and a `source` of
+/// type `S`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSyncResolverEvent<A = Value, S = Value> {
+    /// The arguments of the field.
+    pub arguments: A,
+    /// The caller, or `None` for API key authorization.
+    #[serde(default)]
+    pub identity: Option<Identity>,
+    /// The object the field belongs to, or `None` for fields of the root types.
+    pub source: Option<S>,
+    #[serde(default)]
+    pub request: Option<Request>,
+    pub info: Info,
+    /// The result of the previous function, in pipeline resolvers.
+    #[serde(default)]
+    pub prev: Option<Value>,
+    /// Values shared by the functions of a pipeline resolver.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub stash: HashMap<String, Value>,
+}
+
+/// The caller of a GraphQL operation, which depends on the authorization type of the API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Identity {
+    Iam(IamIdentity),
+    Cognito(CognitoIdentity),
+    Oidc(OidcIdentity),
+    /// Any other identity, such as the `resolverContext` of a Lambda authorizer.
+    Other(Value),
+}
+
+/// A caller signed with AWS IAM credentials.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IamIdentity {
+    pub account_id: String,
+    pub user_arn: String,
+    pub username: String,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub source_ip: Vec<String>,
+    #[serde(default)]
+    pub cognito_identity_pool_id: Option<String>,
+    #[serde(default)]
+    pub cognito_identity_id: Option<String>,
+    #[serde(default)]
+    pub cognito_identity_auth_type: Option<String>,
+    #[serde(default)]
+    pub cognito_identity_auth_provider: Option<String>,
+}
+
+/// A caller signed in to a Cognito user pool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoIdentity {
+    pub sub: String,
+    pub issuer: String,
+    pub username: String,
+    pub claims: HashMap<String, Value>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub source_ip: Vec<String>,
+    /// `ALLOW` or `DENY`.
+    pub default_auth_strategy: String,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub groups: Vec<String>,
+}
+
+/// A caller with a token from an OpenID Connect provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcIdentity {
+    pub sub: String,
+    pub issuer: String,
+    pub claims: HashMap<String, Value>,
+}
+
+/// The HTTP request AppSync received.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub headers: HashMap<String, String>,
+    /// The custom domain name, if the API was called through one.
+    #[serde(default)]
+    pub domain_name: Option<String>,
+}
+
+/// The field being resolved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Info {
+    pub field_name: String,
+    pub parent_type_name: String,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub variables: HashMap<String, Value>,
+    /// The fields selected under this field, such as `author/name`.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub selection_set_list: Vec<String>,
+    #[serde(default, rename = "selectionSetGraphQL")]
+    pub selection_set_graphql: Option<String>,
+}
+
+type Resolver<'a> = Box<dyn FnMut(Value, &LambdaContext) -> LambdaResult<Value> + Send + 'a>;
+
+/// Calls a different function depending on the parent type and name of the field being resolved.
+#[derive(Default)]
+pub struct ResolverRouter<'a> {
+    resolvers: HashMap<(String, String), Resolver<'a>>,
+    fallback: Option<Resolver<'a>>,
+}
+
+impl<'a> ResolverRouter<'a> {
+    pub fn new() -> ResolverRouter<'a> {
+        ResolverRouter::default()
+    }
+
+    /// Resolves the field `field_name` of `parent_type_name` with `f`.
+    pub fn route<T, N, A, S, R, F>(mut self, parent_type_name: T, field_name: N, mut f: F) -> Self
+    where
+        T: Into<String>,
+        N: Into<String>,
+        A: DeserializeOwned,
+        S: DeserializeOwned,
+        R: Serialize,
+        F: FnMut(AppSyncResolverEvent<A, S>, &LambdaContext) -> LambdaResult<R> + Send + 'a,
+    {
+        let resolver: Resolver<'a> = Box::new(move |event, context| {
+            let event = serde_json::from_value(event).map_err(|e| e.to_string())?;
+            let result = f(event, context)?;
+            Ok(serde_json::to_value(result).map_err(|e| e.to_string())?)
+        });
+        self.resolvers
+            .insert((parent_type_name.into(), field_name.into()), resolver);
+        self
+    }
+
+    /// Calls `f` with the raw event for fields without a route. Without a fallback, these fields
+    /// are an error.
+    pub fn fallback<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(Value, &LambdaContext) -> LambdaResult<Value> + Send + 'a,
+    {
+        self.fallback = Some(Box::new(f));
+        self
+    }
+
+    /// Resolves the field for `event`, or each field of a batch. A field of a batch that fails
+    /// gets an error entry instead of a value.
+    pub fn handle(&mut self, event: Value, context: LambdaContext) -> LambdaResult<Value> {
+        match event {
+            Value::Array(events) => Ok(events
+                .into_iter()
+                .map(|event| match self.resolve(event, &context) {
+                    Ok(data) => json!({ "data": data }),
+                    Err(e) => json!({
+                        "data": null,
+                        "errorMessage": e.to_string(),
+                        "errorType": "ResolverError",
+                    }),
+                })
+                .collect()),
+            event => self.resolve(event, &context),
+        }
+    }
+
+    fn resolve(&mut self, event: Value, context: &LambdaContext) -> LambdaResult<Value> {
+        let key = {
+            let info = &event["info"];
+            (
+                info["parentTypeName"].as_str().unwrap_or_default().to_string(),
+                info["fieldName"].as_str().unwrap_or_default().to_string(),
+            )
+        };
+        match self.resolvers.get_mut(&key).or(self.fallback.as_mut()) {
+            Some(resolver) => resolver(event, context),
+            None => Err(format!("no resolver for {}.{}", key.0, key.1).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use LambdaContext;
+
+    fn event(parent: &str, field: &str, arguments: Value, source: Value) -> Value {
+        json!({
+            "arguments": arguments,
+            "identity": {
+                "sub": "f0d4cbc4-2a8b-4a6c-8a8c-9b0b5e3b2a44",
+                "issuer": "https://cognito-idp.us-east-1.amazonaws.com/us-east-1_example",
+                "username": "crowbar",
+                "claims": {"email": "crowbar@example.com"},
+                "sourceIp": ["203.0.113.7"],
+                "defaultAuthStrategy": "ALLOW",
+                "groups": null
+            },
+            "source": source,
+            "request": {"headers": {"x-api-version": "2"}},
+            "info": {
+                "fieldName": field,
+                "parentTypeName": parent,
+                "variables": {},
+                "selectionSetList": ["id"],
+                "selectionSetGraphQL": "{ id }"
+            },
+            "prev": null,
+            "stash": {}
+        })
+    }
+
+    #[derive(Deserialize)]
+    struct GetPost {
+        id: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct Post {
+        id: u32,
+    }
+
+    fn router<'a>() -> ResolverRouter<'a> {
+        ResolverRouter::new()
+            .route("Query", "getPost", |event: AppSyncResolverEvent<GetPost>, _| {
+                Ok(json!({"id": event.arguments.id}))
+            })
+            .route("Post", "title", |event: AppSyncResolverEvent<Value, Post>, _| {
+                match event.source {
+                    Some(post) => Ok(format!("Post {}", post.id)),
+                    None => Err("missing source".into()),
+                }
+            })
+    }
+
+    #[test]
+    fn deserialize() {
+        let event: AppSyncResolverEvent<Value, Value> =
+            serde_json::from_value(event("Query", "getPost", json!({"id": 1}), Value::Null))
+                .unwrap();
+        assert_eq!(event.info.field_name, "getPost");
+        assert_eq!(event.source, None);
+        assert_eq!(event.request.unwrap().headers["x-api-version"], "2");
+        match event.identity {
+            Some(Identity::Cognito(ref identity)) => assert_eq!(identity.username, "crowbar"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let iam: Identity = serde_json::from_value(json!({
+            "accountId": "123456789012",
+            "userArn": "arn:aws:iam::123456789012:user/crowbar",
+            "username": "AIDAEXAMPLE",
+            "sourceIp": ["203.0.113.7"]
+        })).unwrap();
+        match iam {
+            Identity::Iam(ref identity) => assert_eq!(identity.account_id, "123456789012"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn routes() {
+        let get_post = event("Query", "getPost", json!({"id": 1}), Value::Null);
+        assert_eq!(
+            router().handle(get_post, LambdaContext::fake()).unwrap(),
+            json!({"id": 1})
+        );
+
+        let unknown = event("Query", "listPosts", json!({}), Value::Null);
+        assert!(router().handle(unknown.clone(), LambdaContext::fake()).is_err());
+        let mut fallback = router().fallback(|event, _| Ok(event["info"]["fieldName"].clone()));
+        assert_eq!(
+            fallback.handle(unknown, LambdaContext::fake()).unwrap(),
+            "listPosts"
+        );
+    }
+
+    #[test]
+    fn batch() {
+        let mut router = router();
+        let batch = json!([
+            event("Post", "title", json!({}), json!({"id": 1})),
+            event("Post", "title", json!({}), json!({"id": 2})),
+        ]);
+        assert_eq!(
+            router.handle(batch, LambdaContext::fake()).unwrap(),
+            json!([{"data": "Post 1"}, {"data": "Post 2"}])
+        );
+
+        let failing = json!([
+            event("Post", "title", json!({}), json!({"id": 1})),
+            event("Post", "title", json!({}), Value::Null),
+        ]);
+        assert_eq!(
+            router.handle(failing, LambdaContext::fake()).unwrap(),
+            json!([
+                {"data": "Post 1"},
+                {"data": null, "errorMessage": "missing source", "errorType": "ResolverError"},
+            ])
+        );
+    }
+}
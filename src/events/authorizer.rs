@@ -0,0 +1,430 @@
+//! API Gateway custom authorizer events and responses.
+//!
+//! API Gateway invokes a [Lambda authorizer]
+//! (https://docs.aws.amazon.com/apigateway/latest/developerguide/apigateway-use-lambda-authorizer.html)
+//! with either the bearer token (`TOKEN` authorizers) or the request parameters (`REQUEST`
+//! authorizers) and expects an IAM policy back. To reject the caller with a `401 Unauthorized`,
+//! return the `Unauthorized` error.
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::authorizer::{AuthorizerResponse, TokenAuthorizerEvent, Unauthorized};
+//!
+//! lambda!(|event: TokenAuthorizerEvent, _context| {
+//!     if event.authorization_token != "allow" {
+//!         return Err(Unauthorized.into());
+//!     }
+//!     let arn = event.method_arn.parse::<crowbar::events::authorizer::MethodArn>()?;
+//!     Ok(AuthorizerResponse::new("user")
+//!         .allow(arn.with_method("GET").with_resource("pets/*"))
+//!         .deny(arn.all())
+//!         .with_context("plan", "free"))
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+/// The event a Lambda function receives as an API Gateway authorizer, of either type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AuthorizerEvent {
+    #[serde(rename = "TOKEN")]
+    Token(TokenAuthorizerEvent),
+    #[serde(rename = "REQUEST")]
+    Request(Box<RequestAuthorizerEvent>),
+}
+
+impl AuthorizerEvent {
+    /// The ARN of the method being called, regardless of the authorizer type.
+    pub fn method_arn(&self) -> &str {
+        match *self {
+            AuthorizerEvent::Token(ref event) => &event.method_arn,
+            AuthorizerEvent::Request(ref event) => &event.method_arn,
+        }
+    }
+}
+
+/// The event a Lambda function receives as a `TOKEN` authorizer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenAuthorizerEvent {
+    /// The value of the header configured as the token source.
+    pub authorization_token: String,
+    pub method_arn: String,
+}
+
+/// The event a Lambda function receives as a `REQUEST` authorizer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestAuthorizerEvent {
+    pub method_arn: String,
+    pub resource: String,
+    pub path: String,
+    pub http_method: String,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub headers: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub query_string_parameters: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub path_parameters: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub stage_variables: HashMap<String, String>,
+    #[serde(default)]
+    pub request_context: Value,
+}
+
+/// An API Gateway method ARN, such as
+/// `arn:aws:execute-api:us-east-1:123456789012:abcdef1234/prod/GET/pets/cats`.
+///
+/// Policies usually cover more than the method being called, so that API Gateway can cache them
+/// across requests. `with_method`, `with_resource` and `all` build the ARNs for other methods of
+/// the same API and stage, and accept `*` wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodArn {
+    /// The AWS partition, such as `aws` or `aws-cn`.
+    pub partition: String,
+    pub region: String,
+    pub account_id: String,
+    pub api_id: String,
+    pub stage: String,
+    pub http_method: String,
+    /// The resource path, without the leading `/`.
+    pub resource: String,
+}
+
+impl MethodArn {
+    /// Returns this ARN with the HTTP method replaced.
+    pub fn with_method<S>(&self, http_method: S) -> MethodArn
+    where
+        S: Into<String>,
+    {
+        MethodArn {
+            http_method: http_method.into(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns this ARN with the resource path replaced.
+    pub fn with_resource<S>(&self, resource: S) -> MethodArn
+    where
+        S: Into<String>,
+    {
+        MethodArn {
+            resource: resource.into().trim_start_matches('/').to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns an ARN matching every method and resource of this API and stage.
+    pub fn all(&self) -> MethodArn {
+        self.with_method("*").with_resource("*")
+    }
+}
+
+impl FromStr for MethodArn {
+    type Err = InvalidMethodArn;
+
+    fn from_str(s: &str) -> Result<MethodArn, InvalidMethodArn> {
+        let parts: Vec<&str> = s.splitn(6, ':').collect();
+        if parts.len() != 6 || parts[0] != "arn" || parts[2] != "execute-api" {
+            return Err(InvalidMethodArn(s.to_string()));
+        }
+        let path: Vec<&str> = parts[5].splitn(4, '/').collect();
+        if path.len() < 3 {
+            return Err(InvalidMethodArn(s.to_string()));
+        }
+        Ok(MethodArn {
+            partition: parts[1].to_string(),
+            region: parts[3].to_string(),
+            account_id: parts[4].to_string(),
+            api_id: path[0].to_string(),
+            stage: path[1].to_string(),
+            http_method: path[2].to_string(),
+            resource: path.get(3).cloned().unwrap_or("").to_string(),
+        })
+    }
+}
+
+impl fmt::Display for MethodArn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "arn:{}:execute-api:{}:{}:{}/{}/{}/{}",
+            self.partition,
+            self.region,
+            self.account_id,
+            self.api_id,
+            self.stage,
+            self.http_method,
+            self.resource
+        )
+    }
+}
+
+impl From<MethodArn> for String {
+    fn from(arn: MethodArn) -> String {
+        arn.to_string()
+    }
+}
+
+/// Error returned when parsing a string that isn't an API Gateway method ARN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidMethodArn(pub String);
+
+impl fmt::Display for InvalidMethodArn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid method ARN: {}", self.0)
+    }
+}
+
+impl ::std::error::Error for InvalidMethodArn {
+    fn description(&self) -> &str {
+        "invalid method ARN"
+    }
+}
+
+#[cfg(feature = "error-chain")]
+impl From<InvalidMethodArn> for ::Error {
+    fn from(e: InvalidMethodArn) -> ::Error {
+        e.to_string().into()
+    }
+}
+
+/// Error that rejects the caller of an API with a `401 Unauthorized` response.
+///
+/// API Gateway only responds with a 401 if the authorizer fails with the exact message
+/// `Unauthorized`; crowbar raises this error as a Python `Exception("Unauthorized")`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unauthorized;
+
+impl fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unauthorized")
+    }
+}
+
+impl ::std::error::Error for Unauthorized {
+    fn description(&self) -> &str {
+        "Unauthorized"
+    }
+}
+
+#[cfg(feature = "error-chain")]
+impl From<Unauthorized> for ::Error {
+    fn from(_: Unauthorized) -> ::Error {
+        ::PyException("Unauthorized".to_string()).into()
+    }
+}
+
+/// The response an authorizer returns to API Gateway.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorizerResponse {
+    /// Identifies the caller; available to the integration as
+    /// `$context.authorizer.principalId`.
+    pub principal_id: String,
+    pub policy_document: PolicyDocument,
+    /// Values available to the integration as `$context.authorizer.<key>`. API Gateway only
+    /// accepts strings, numbers and booleans.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub context: HashMap<String, Value>,
+    /// The API key to use for usage plans, if the API key source is `AUTHORIZER`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_identifier_key: Option<String>,
+}
+
+impl AuthorizerResponse {
+    /// Creates a response for `principal_id` with an empty policy, which denies everything.
+    pub fn new<S>(principal_id: S) -> AuthorizerResponse
+    where
+        S: Into<String>,
+    {
+        AuthorizerResponse {
+            principal_id: principal_id.into(),
+            policy_document: PolicyDocument::default(),
+            context: HashMap::new(),
+            usage_identifier_key: None,
+        }
+    }
+
+    /// Allows invoking the methods matched by `resource`, a method ARN.
+    pub fn allow<S>(self, resource: S) -> AuthorizerResponse
+    where
+        S: Into<String>,
+    {
+        self.statement(Effect::Allow, resource.into())
+    }
+
+    /// Denies invoking the methods matched by `resource`, a method ARN. Denials take precedence
+    /// over allows.
+    pub fn deny<S>(self, resource: S) -> AuthorizerResponse
+    where
+        S: Into<String>,
+    {
+        self.statement(Effect::Deny, resource.into())
+    }
+
+    /// Adds a value to the context passed to the integration.
+    pub fn with_context<K, V>(mut self, key: K, value: V) -> AuthorizerResponse
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.context.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the API key used for usage plans.
+    pub fn with_usage_identifier_key<S>(mut self, key: S) -> AuthorizerResponse
+    where
+        S: Into<String>,
+    {
+        self.usage_identifier_key = Some(key.into());
+        self
+    }
+
+    fn statement(mut self, effect: Effect, resource: String) -> AuthorizerResponse {
+        // group resources with the same effect into one statement to keep the policy small
+        let existing = self.policy_document
+            .statement
+            .iter_mut()
+            .find(|statement| statement.effect == effect);
+        match existing {
+            Some(statement) => statement.resource.push(resource),
+            None => self.policy_document.statement.push(Statement {
+                action: "execute-api:Invoke".to_string(),
+                effect: effect,
+                resource: vec![resource],
+            }),
+        }
+        self
+    }
+}
+
+/// An IAM policy document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyDocument {
+    pub version: String,
+    pub statement: Vec<Statement>,
+}
+
+impl Default for PolicyDocument {
+    fn default() -> PolicyDocument {
+        PolicyDocument {
+            version: "2012-10-17".to_string(),
+            statement: vec![],
+        }
+    }
+}
+
+/// A statement in an IAM policy document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Statement {
+    pub action: String,
+    pub effect: Effect,
+    pub resource: Vec<String>,
+}
+
+/// Whether a policy statement allows or denies access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn event_types() {
+        let token: AuthorizerEvent = serde_json::from_value(json!({
+            "type": "TOKEN",
+            "authorizationToken": "allow",
+            "methodArn": "arn:aws:execute-api:us-east-1:123456789012:abcdef1234/prod/GET/pets"
+        })).unwrap();
+        match token {
+            AuthorizerEvent::Token(ref event) => assert_eq!(event.authorization_token, "allow"),
+            _ => panic!("expected a TOKEN event"),
+        }
+
+        let request: AuthorizerEvent = serde_json::from_value(json!({
+            "type": "REQUEST",
+            "methodArn": "arn:aws:execute-api:us-east-1:123456789012:abcdef1234/prod/GET/pets",
+            "resource": "/pets",
+            "path": "/pets",
+            "httpMethod": "GET",
+            "headers": {"Authorization": "allow"},
+            "queryStringParameters": null
+        })).unwrap();
+        match request {
+            AuthorizerEvent::Request(ref event) => {
+                assert_eq!(event.headers["Authorization"], "allow");
+                assert!(event.query_string_parameters.is_empty());
+            }
+            _ => panic!("expected a REQUEST event"),
+        }
+        assert_eq!(request.method_arn(), token.method_arn());
+    }
+
+    #[test]
+    fn method_arn() {
+        let s = "arn:aws:execute-api:us-east-1:123456789012:abcdef1234/prod/GET/pets/cats";
+        let arn: MethodArn = s.parse().unwrap();
+        assert_eq!(arn.api_id, "abcdef1234");
+        assert_eq!(arn.stage, "prod");
+        assert_eq!(arn.http_method, "GET");
+        assert_eq!(arn.resource, "pets/cats");
+        assert_eq!(arn.to_string(), s);
+        assert_eq!(
+            arn.with_method("POST").with_resource("/pets").to_string(),
+            "arn:aws:execute-api:us-east-1:123456789012:abcdef1234/prod/POST/pets"
+        );
+        assert_eq!(
+            arn.all().to_string(),
+            "arn:aws:execute-api:us-east-1:123456789012:abcdef1234/prod/*/*"
+        );
+        assert!("arn:aws:lambda:us-east-1:123456789012:function:f".parse::<MethodArn>().is_err());
+
+        let s = "arn:aws-cn:execute-api:cn-north-1:123456789012:abcdef1234/prod/GET/pets";
+        let arn: MethodArn = s.parse().unwrap();
+        assert_eq!(arn.partition, "aws-cn");
+        assert_eq!(arn.to_string(), s);
+        assert_eq!(
+            arn.all().to_string(),
+            "arn:aws-cn:execute-api:cn-north-1:123456789012:abcdef1234/prod/*/*"
+        );
+    }
+
+    #[test]
+    fn response() {
+        let response = AuthorizerResponse::new("user")
+            .allow("arn:1")
+            .deny("arn:2")
+            .allow("arn:3")
+            .with_context("admin", false);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "principalId": "user",
+                "policyDocument": {
+                    "Version": "2012-10-17",
+                    "Statement": [
+                        {"Action": "execute-api:Invoke", "Effect": "Allow", "Resource": ["arn:1", "arn:3"]},
+                        {"Action": "execute-api:Invoke", "Effect": "Deny", "Resource": ["arn:2"]}
+                    ]
+                },
+                "context": {"admin": false}
+            })
+        );
+    }
+}
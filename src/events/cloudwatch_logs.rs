@@ -0,0 +1,125 @@
+//! CloudWatch Logs subscription events.
+//!
+//! CloudWatch Logs delivers the log events matched by a [subscription filter]
+//! (https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/SubscriptionFilters.html#LambdaFunctionExample)
+//! as gzipped, base64-encoded JSON under `awslogs.data`. crowbar decodes the payload while
+//! deserializing the event, so handlers only ever see the `LogsEvent` inside.
+
+use std::io::{Read, Write};
+
+use base64;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+use serde_json;
+
+/// The event a Lambda function receives from a CloudWatch Logs subscription.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudWatchLogsEvent {
+    #[serde(rename = "awslogs")]
+    pub aws_logs: AwsLogs,
+}
+
+/// Container for the encoded log data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AwsLogs {
+    /// The decoded contents of `awslogs.data`.
+    #[serde(deserialize_with = "decode_data", serialize_with = "encode_data")]
+    pub data: LogsEvent,
+}
+
+/// The log events delivered for one log stream by a subscription filter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsEvent {
+    /// `DATA_MESSAGE` for log data, or `CONTROL_MESSAGE` for messages CloudWatch Logs sends to
+    /// check that the destination is reachable.
+    pub message_type: String,
+    /// The AWS account ID of the originating log data.
+    pub owner: String,
+    /// The log group name of the originating log data.
+    pub log_group: String,
+    /// The log stream name of the originating log data.
+    pub log_stream: String,
+    /// The names of the subscription filters that matched the originating log data.
+    pub subscription_filters: Vec<String>,
+    /// The log events themselves.
+    pub log_events: Vec<LogEntry>,
+}
+
+/// A single log event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Unique identifier of the log event.
+    pub id: String,
+    /// Time the event was logged, in milliseconds since the Unix epoch.
+    pub timestamp: i64,
+    /// The raw log message.
+    pub message: String,
+}
+
+fn decode_data<'de, D>(deserializer: D) -> Result<LogsEvent, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let encoded = String::deserialize(deserializer)?;
+    let compressed = base64::decode(&encoded).map_err(de::Error::custom)?;
+    let mut json = Vec::new();
+    GzDecoder::new(&compressed[..])
+        .read_to_end(&mut json)
+        .map_err(de::Error::custom)?;
+    serde_json::from_slice(&json).map_err(de::Error::custom)
+}
+
+fn encode_data<S>(data: &LogsEvent, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let json = serde_json::to_vec(data).map_err(ser::Error::custom)?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json).map_err(ser::Error::custom)?;
+    let compressed = encoder.finish().map_err(ser::Error::custom)?;
+    base64::encode(&compressed).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = r#"{
+        "awslogs": {
+            "data": "H4sIAAAAAAACAz2QQW+DMAyF/0oV7dgpcUIg6Q2prJftBLcKTQHSghQIImFoqvrf53baLpb8nvzZzzcy2hDM1VbfsyUHcsyr/POjKMv8VJA98dtkF5SBi0SmmdIMOMrOX0+LX2d0qNkCdWZsOkNt2/tft4yLNSPanEFGAShT9PzynldFWdXsn2aatrMXHAlrE9plmOPgp7fBRbsEcjiTB/D18uxJ/QQXX3aKD+9Ghg75QqSSaZAJF0hVCUs0FkghS5TSQgFPQUrBM8GVEFqLTKVa8QR3xgGjRzNiCpDAgGnOFI7u/16C+N4653et82u3mdj2OzwhkHt9/wE/zqz+OAEAAA=="
+        }
+    }"#;
+
+    #[test]
+    fn decode() {
+        let event: CloudWatchLogsEvent = serde_json::from_str(EVENT).unwrap();
+        let data = &event.aws_logs.data;
+        assert_eq!(data.message_type, "DATA_MESSAGE");
+        assert_eq!(data.owner, "123456789012");
+        assert_eq!(data.log_group, "/aws/lambda/echo");
+        assert_eq!(data.subscription_filters, vec!["echo-filter"]);
+        assert_eq!(data.log_events.len(), 1);
+        assert_eq!(data.log_events[0].timestamp, 1_510_109_208_016);
+        assert_eq!(data.log_events[0].message, "hello cloudwatch logs");
+    }
+
+    #[test]
+    fn round_trip() {
+        let event: CloudWatchLogsEvent = serde_json::from_str(EVENT).unwrap();
+        let encoded = serde_json::to_value(&event).unwrap();
+        assert!(encoded["awslogs"]["data"].is_string());
+        assert_eq!(serde_json::from_value::<CloudWatchLogsEvent>(encoded).unwrap(), event);
+    }
+
+    #[test]
+    fn invalid_data() {
+        let result = serde_json::from_str::<CloudWatchLogsEvent>(
+            r#"{"awslogs": {"data": "not gzip"}}"#,
+        );
+        assert!(result.is_err());
+    }
+}
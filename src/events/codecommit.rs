@@ -0,0 +1,104 @@
+//! CodeCommit repository trigger events.
+//!
+//! A [repository trigger](https://docs.aws.amazon.com/codecommit/latest/userguide/how-to-notify-lambda.html)
+//! invokes a function when references in the repository change, such as a push to a branch.
+
+/// The event a Lambda function receives from a CodeCommit trigger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeCommitEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<CodeCommitRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeCommitRecord {
+    pub event_id: String,
+    pub event_version: String,
+    /// When the references changed, in ISO 8601 format.
+    pub event_time: String,
+    pub event_trigger_name: String,
+    pub event_trigger_config_id: String,
+    /// Always `aws:codecommit`.
+    pub event_source: String,
+    /// The ARN of the repository.
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn: String,
+    /// `ReferenceChanges`, or `TriggerEventTest` for tests from the console.
+    pub event_name: String,
+    pub event_part_number: u64,
+    pub event_total_parts: u64,
+    pub aws_region: String,
+    #[serde(rename = "userIdentityARN")]
+    pub user_identity_arn: String,
+    /// The custom data configured on the trigger.
+    #[serde(default)]
+    pub custom_data: Option<String>,
+    pub codecommit: CodeCommit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeCommit {
+    pub references: Vec<Reference>,
+}
+
+/// A reference that changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    /// The commit the reference points to.
+    pub commit: String,
+    /// The full name of the reference, such as `refs/heads/master`.
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default)]
+    pub created: bool,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+impl Reference {
+    /// The branch name, if the reference is a branch.
+    pub fn branch(&self) -> Option<&str> {
+        if self.reference.starts_with("refs/heads/") {
+            Some(&self.reference["refs/heads/".len()..])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn deserialize() {
+        let event: CodeCommitEvent = serde_json::from_value(json!({
+            "Records": [{
+                "awsRegion": "us-east-2",
+                "codecommit": {
+                    "references": [{
+                        "commit": "5e493c6f3067653f3d04eca608b4901eb227078",
+                        "ref": "refs/heads/master"
+                    }]
+                },
+                "customData": null,
+                "eventId": "31ade2c7-f889-47c5-a937-1cf99e2790e9",
+                "eventName": "ReferenceChanges",
+                "eventPartNumber": 1,
+                "eventSource": "aws:codecommit",
+                "eventSourceARN": "arn:aws:codecommit:us-east-2:123456789012:lambda-pipeline-repo",
+                "eventTime": "2019-03-12T20:58:25.400+0000",
+                "eventTotalParts": 1,
+                "eventTriggerConfigId": "0d17d6a4-efeb-46f3-b3ab-a63741badeb8",
+                "eventTriggerName": "index.handler",
+                "eventVersion": "1.0",
+                "userIdentityARN": "arn:aws:iam::123456789012:user/intern"
+            }]
+        })).unwrap();
+        let reference = &event.records[0].codecommit.references[0];
+        assert_eq!(reference.branch(), Some("master"));
+        assert!(!reference.created);
+    }
+}
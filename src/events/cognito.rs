@@ -0,0 +1,470 @@
+//! Cognito User Pool trigger events.
+//!
+//! Cognito invokes [User Pool triggers]
+//! (https://docs.aws.amazon.com/cognito/latest/developerguide/cognito-user-identity-pools-working-with-aws-lambda-triggers.html)
+//! with an event that has a `request` and a `response` section, and expects the same event back
+//! with the `response` section filled in. `CognitoEvent::respond` only gives the handler access
+//! to `response`, and echoes everything else back unchanged.
+//!
+//! A function that handles several triggers can use `CognitoTrigger`, which picks the event type
+//! from `triggerSource`:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::cognito::CognitoTrigger;
+//!
+//! lambda!(|event: CognitoTrigger, _context| match event {
+//!     CognitoTrigger::PreSignUp(event) => event
+//!         .respond(|request, response| {
+//!             let email = request.user_attributes.get("email").map(String::as_str);
+//!             if !email.unwrap_or("").ends_with("@example.com") {
+//!                 return Err("sign up is restricted to example.com".into());
+//!             }
+//!             response.auto_confirm_user = true;
+//!             Ok(())
+//!         })
+//!         .map(CognitoTrigger::PreSignUp),
+//!     other => Ok(other),
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer};
+use serde_json::{self, Value};
+
+use LambdaResult;
+
+/// The event a Lambda function receives from a User Pool trigger, with typed `request` and
+/// `response` sections.
+///
+/// The `request` section is serialized from `raw_request`, as Cognito sent it, rather than from
+/// `request`, so fields crowbar doesn't know about survive the round trip.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CognitoEvent<Req, Res> {
+    pub version: String,
+    /// The trigger and the operation that caused it, such as `PreSignUp_AdminCreateUser`.
+    pub trigger_source: String,
+    pub region: String,
+    pub user_pool_id: String,
+    pub user_name: Option<String>,
+    pub caller_context: CallerContext,
+    /// The `request` section, deserialized from `raw_request`. Changes to it aren't sent back.
+    #[serde(skip)]
+    pub request: Req,
+    /// The `request` section as Cognito sent it, which is what's sent back. An event built by hand
+    /// needs to set it along with `request`, such as with `serde_json::to_value(&request)`.
+    #[serde(rename = "request")]
+    pub raw_request: Value,
+    pub response: Res,
+    /// Fields crowbar doesn't know about, which are echoed back as-is.
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+
+impl<'de, Req, Res> Deserialize<'de> for CognitoEvent<Req, Res>
+where
+    Req: DeserializeOwned,
+    Res: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<CognitoEvent<Req, Res>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Fields<Res> {
+            version: String,
+            trigger_source: String,
+            region: String,
+            user_pool_id: String,
+            user_name: Option<String>,
+            caller_context: CallerContext,
+            request: Value,
+            response: Res,
+            #[serde(flatten)]
+            other: HashMap<String, Value>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        Ok(CognitoEvent {
+            version: fields.version,
+            trigger_source: fields.trigger_source,
+            region: fields.region,
+            user_pool_id: fields.user_pool_id,
+            user_name: fields.user_name,
+            caller_context: fields.caller_context,
+            request: serde_json::from_value(fields.request.clone()).map_err(de::Error::custom)?,
+            raw_request: fields.request,
+            response: fields.response,
+            other: fields.other,
+        })
+    }
+}
+
+impl<Req, Res> CognitoEvent<Req, Res> {
+    /// Lets `f` fill in the response and returns the event to send back to Cognito.
+    pub fn respond<F>(mut self, f: F) -> LambdaResult<Self>
+    where
+        F: FnOnce(&Req, &mut Res) -> LambdaResult<()>,
+    {
+        f(&self.request, &mut self.response)?;
+        Ok(self)
+    }
+}
+
+/// Information about the client that caused the trigger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallerContext {
+    pub aws_sdk_version: String,
+    pub client_id: String,
+}
+
+pub type PreSignUpEvent = CognitoEvent<PreSignUpRequest, PreSignUpResponse>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreSignUpRequest {
+    pub user_attributes: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub validation_data: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub client_metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreSignUpResponse {
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub auto_confirm_user: bool,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub auto_verify_phone: bool,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub auto_verify_email: bool,
+}
+
+pub type PreTokenGenerationEvent =
+    CognitoEvent<PreTokenGenerationRequest, PreTokenGenerationResponse>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreTokenGenerationRequest {
+    pub user_attributes: HashMap<String, String>,
+    pub group_configuration: GroupConfiguration,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub client_metadata: HashMap<String, String>,
+}
+
+/// The groups and IAM roles in the token, which `GroupOverrideDetails` can replace.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupConfiguration {
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub groups_to_override: Vec<String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub iam_roles_to_override: Vec<String>,
+    #[serde(default)]
+    pub preferred_role: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreTokenGenerationResponse {
+    #[serde(default)]
+    pub claims_override_details: Option<ClaimsOverrideDetails>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimsOverrideDetails {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub claims_to_add_or_override: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub claims_to_suppress: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_override_details: Option<GroupConfiguration>,
+}
+
+pub type CustomMessageEvent = CognitoEvent<CustomMessageRequest, CustomMessageResponse>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomMessageRequest {
+    pub user_attributes: HashMap<String, String>,
+    /// Placeholder to put in the message where the verification code goes.
+    pub code_parameter: Option<String>,
+    /// Placeholder to put in the message where the user name goes.
+    pub username_parameter: Option<String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub client_metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomMessageResponse {
+    #[serde(default)]
+    pub sms_message: Option<String>,
+    #[serde(default)]
+    pub email_message: Option<String>,
+    #[serde(default)]
+    pub email_subject: Option<String>,
+}
+
+pub type DefineAuthChallengeEvent =
+    CognitoEvent<DefineAuthChallengeRequest, DefineAuthChallengeResponse>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefineAuthChallengeRequest {
+    pub user_attributes: HashMap<String, String>,
+    /// The challenges the user has been presented with so far in this authentication flow.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub session: Vec<ChallengeResult>,
+    #[serde(default)]
+    pub user_not_found: bool,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub client_metadata: HashMap<String, String>,
+}
+
+/// A challenge presented earlier in the authentication flow, and whether the user answered it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeResult {
+    pub challenge_name: String,
+    pub challenge_result: bool,
+    #[serde(default)]
+    pub challenge_metadata: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefineAuthChallengeResponse {
+    #[serde(default)]
+    pub challenge_name: Option<String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub issue_tokens: bool,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub fail_authentication: bool,
+}
+
+pub type CreateAuthChallengeEvent =
+    CognitoEvent<CreateAuthChallengeRequest, CreateAuthChallengeResponse>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAuthChallengeRequest {
+    pub user_attributes: HashMap<String, String>,
+    pub challenge_name: String,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub session: Vec<ChallengeResult>,
+    #[serde(default)]
+    pub user_not_found: bool,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub client_metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAuthChallengeResponse {
+    /// Sent to the client.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub public_challenge_parameters: HashMap<String, String>,
+    /// Passed to the verify auth challenge trigger, typically with the expected answer.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub private_challenge_parameters: HashMap<String, String>,
+    #[serde(default)]
+    pub challenge_metadata: Option<String>,
+}
+
+pub type VerifyAuthChallengeEvent =
+    CognitoEvent<VerifyAuthChallengeRequest, VerifyAuthChallengeResponse>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyAuthChallengeRequest {
+    pub user_attributes: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub private_challenge_parameters: HashMap<String, String>,
+    pub challenge_answer: String,
+    #[serde(default)]
+    pub user_not_found: bool,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub client_metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyAuthChallengeResponse {
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub answer_correct: bool,
+}
+
+/// Any User Pool trigger event, chosen by the prefix of `triggerSource`.
+///
+/// Triggers crowbar doesn't have types for are deserialized as `Other`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum CognitoTrigger {
+    PreSignUp(PreSignUpEvent),
+    PreTokenGeneration(PreTokenGenerationEvent),
+    CustomMessage(CustomMessageEvent),
+    DefineAuthChallenge(DefineAuthChallengeEvent),
+    CreateAuthChallenge(CreateAuthChallengeEvent),
+    VerifyAuthChallenge(VerifyAuthChallengeEvent),
+    Other(Value),
+}
+
+impl<'de> Deserialize<'de> for CognitoTrigger {
+    fn deserialize<D>(deserializer: D) -> Result<CognitoTrigger, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        fn typed<T, E>(value: Value) -> Result<T, E>
+        where
+            T: DeserializeOwned,
+            E: de::Error,
+        {
+            serde_json::from_value(value).map_err(E::custom)
+        }
+
+        let value = Value::deserialize(deserializer)?;
+        let trigger = value["triggerSource"]
+            .as_str()
+            .and_then(|source| source.split('_').next())
+            .unwrap_or("")
+            .to_string();
+        Ok(match trigger.as_str() {
+            "PreSignUp" => CognitoTrigger::PreSignUp(typed(value)?),
+            "TokenGeneration" => CognitoTrigger::PreTokenGeneration(typed(value)?),
+            "CustomMessage" => CognitoTrigger::CustomMessage(typed(value)?),
+            "DefineAuthChallenge" => CognitoTrigger::DefineAuthChallenge(typed(value)?),
+            "CreateAuthChallenge" => CognitoTrigger::CreateAuthChallenge(typed(value)?),
+            "VerifyAuthChallengeResponse" => CognitoTrigger::VerifyAuthChallenge(typed(value)?),
+            _ => CognitoTrigger::Other(value),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{self, Value};
+
+    fn event(trigger_source: &str, request: Value, response: Value) -> Value {
+        json!({
+            "version": "1",
+            "triggerSource": trigger_source,
+            "region": "us-east-1",
+            "userPoolId": "us-east-1_example",
+            "userName": "crowbar",
+            "callerContext": {"awsSdkVersion": "aws-sdk-unknown-unknown", "clientId": "client"},
+            "request": request,
+            "response": response,
+            "future": "field"
+        })
+    }
+
+    #[test]
+    fn dispatch() {
+        let pre_sign_up = event(
+            "PreSignUp_SignUp",
+            json!({"userAttributes": {"email": "crowbar@example.com"}, "validationData": null}),
+            json!({"autoConfirmUser": false, "autoVerifyEmail": false, "autoVerifyPhone": false}),
+        );
+        match serde_json::from_value(pre_sign_up).unwrap() {
+            CognitoTrigger::PreSignUp(ref e) => {
+                assert_eq!(e.request.user_attributes["email"], "crowbar@example.com")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let verify = event(
+            "VerifyAuthChallengeResponse_Authentication",
+            json!({"userAttributes": {}, "privateChallengeParameters": {"answer": "42"},
+                   "challengeAnswer": "42"}),
+            json!({"answerCorrect": null}),
+        );
+        match serde_json::from_value(verify).unwrap() {
+            CognitoTrigger::VerifyAuthChallenge(ref e) => assert_eq!(e.request.challenge_answer, "42"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let post_confirmation = event("PostConfirmation_ConfirmSignUp", json!({}), json!({}));
+        match serde_json::from_value(post_confirmation.clone()).unwrap() {
+            CognitoTrigger::Other(ref value) => assert_eq!(*value, post_confirmation),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn respond_echoes_event() {
+        let original = event(
+            "CustomMessage_SignUp",
+            json!({"userAttributes": {}, "codeParameter": "{####}", "usernameParameter": null,
+                   "futureRequestField": true}),
+            json!({"smsMessage": null, "emailMessage": null, "emailSubject": null}),
+        );
+        let event: CustomMessageEvent = serde_json::from_value(original.clone()).unwrap();
+        let event = event
+            .respond(|request, response| {
+                let code = request.code_parameter.clone().unwrap();
+                response.email_subject = Some("Welcome".to_string());
+                response.email_message = Some(format!("Your code is {}", code));
+                Ok(())
+            })
+            .unwrap();
+
+        let mut expected = original;
+        expected["response"] = json!({
+            "smsMessage": null,
+            "emailMessage": "Your code is {####}",
+            "emailSubject": "Welcome"
+        });
+        assert_eq!(serde_json::to_value(&event).unwrap(), expected);
+    }
+
+    #[test]
+    fn serialize_built_event() {
+        let request = PreSignUpRequest {
+            user_attributes: vec![("email".to_string(), "crowbar@example.com".to_string())]
+                .into_iter()
+                .collect(),
+            validation_data: HashMap::new(),
+            client_metadata: HashMap::new(),
+        };
+        let built = PreSignUpEvent {
+            version: "1".to_string(),
+            trigger_source: "PreSignUp_SignUp".to_string(),
+            region: "us-east-1".to_string(),
+            user_pool_id: "us-east-1_example".to_string(),
+            user_name: Some("crowbar".to_string()),
+            caller_context: CallerContext {
+                aws_sdk_version: "aws-sdk-unknown-unknown".to_string(),
+                client_id: "client".to_string(),
+            },
+            raw_request: serde_json::to_value(&request).unwrap(),
+            request: request,
+            response: PreSignUpResponse {
+                auto_confirm_user: true,
+                ..Default::default()
+            },
+            other: HashMap::new(),
+        };
+
+        let mut expected = event(
+            "PreSignUp_SignUp",
+            json!({"userAttributes": {"email": "crowbar@example.com"}, "validationData": {},
+                   "clientMetadata": {}}),
+            json!({"autoConfirmUser": true, "autoVerifyEmail": false, "autoVerifyPhone": false}),
+        );
+        expected.as_object_mut().unwrap().remove("future");
+        let serialized = serde_json::to_value(&built).unwrap();
+        assert_eq!(serialized, expected);
+        let parsed: PreSignUpEvent = serde_json::from_value(serialized).unwrap();
+        assert_eq!(parsed, built);
+    }
+}
@@ -0,0 +1,252 @@
+//! EventBridge and CloudWatch Events events, including scheduled events.
+//!
+//! Every [EventBridge event]
+//! (https://docs.aws.amazon.com/eventbridge/latest/userguide/aws-events.html) has the same
+//! envelope, with a `detail` that depends on the `source` and `detail-type`. `EventBridgeEvent`
+//! takes the type of `detail` as a parameter, and `EventRouter` calls a different function
+//! depending on where the event came from. Build the router once and keep it for every
+//! invocation:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use crowbar::{LambdaContext, LambdaResult};
+//! use crowbar::events::eventbridge::{EventBridgeEvent, EventRouter, ScheduledEvent};
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename_all = "kebab-case")]
+//! struct InstanceStateChange {
+//!     instance_id: String,
+//!     state: String,
+//! }
+//!
+//! fn instance_changed(
+//!     event: EventBridgeEvent<InstanceStateChange>,
+//!     _: &LambdaContext,
+//! ) -> LambdaResult<()> {
+//!     println!("{} is now {}", event.detail.instance_id, event.detail.state);
+//!     Ok(())
+//! }
+//!
+//! fn scheduled(event: ScheduledEvent, _: &LambdaContext) -> LambdaResult<()> {
+//!     println!("triggered by {:?} at {}", event.resources, event.time);
+//!     Ok(())
+//! }
+//!
+//! lambda!({
+//!     let mut router = EventRouter::new()
+//!         .route("aws.ec2", "EC2 Instance State-change Notification", instance_changed)
+//!         .route_source("aws.events", scheduled);
+//!     move |event, context| router.handle(event, context)
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use {LambdaContext, LambdaResult};
+
+/// An event delivered by EventBridge, with a `detail` of type `D`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventBridgeEvent<D = Value> {
+    pub version: String,
+    pub id: String,
+    #[serde(rename = "detail-type")]
+    pub detail_type: String,
+    /// The service or application that emitted the event, such as `aws.ec2`.
+    pub source: String,
+    pub account: String,
+    /// When the event occurred, in RFC 3339 format.
+    pub time: String,
+    pub region: String,
+    /// ARNs of the resources involved in the event.
+    #[serde(default)]
+    pub resources: Vec<String>,
+    pub detail: D,
+    /// Fields that only appear in some events, such as `replay-name`.
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+
+/// An event from a schedule rule, which has a `source` of `aws.events`, a `detail-type` of
+/// `Scheduled Event`, the rule in `resources` and an empty `detail`.
+pub type ScheduledEvent = EventBridgeEvent<Value>;
+
+type Route<'a, O> = Box<dyn FnMut(Value, &LambdaContext) -> LambdaResult<O> + Send + 'a>;
+
+/// Calls a different function depending on the `source` and `detail-type` of an event.
+///
+/// Routes are tried in the order they were added.
+pub struct EventRouter<'a, O> {
+    routes: Vec<(String, Option<String>, Route<'a, O>)>,
+    fallback: Option<Route<'a, O>>,
+}
+
+impl<'a, O> Default for EventRouter<'a, O> {
+    fn default() -> EventRouter<'a, O> {
+        EventRouter {
+            routes: vec![],
+            fallback: None,
+        }
+    }
+}
+
+impl<'a, O> EventRouter<'a, O> {
+    pub fn new() -> EventRouter<'a, O> {
+        EventRouter::default()
+    }
+
+    /// Routes events with the given `source` and `detail-type` to `f`.
+    pub fn route<S, T, D, F>(self, source: S, detail_type: T, f: F) -> EventRouter<'a, O>
+    where
+        S: Into<String>,
+        T: Into<String>,
+        D: DeserializeOwned,
+        F: FnMut(EventBridgeEvent<D>, &LambdaContext) -> LambdaResult<O> + Send + 'a,
+    {
+        self.add(source.into(), Some(detail_type.into()), f)
+    }
+
+    /// Routes events with the given `source` and any `detail-type` to `f`.
+    pub fn route_source<S, D, F>(self, source: S, f: F) -> EventRouter<'a, O>
+    where
+        S: Into<String>,
+        D: DeserializeOwned,
+        F: FnMut(EventBridgeEvent<D>, &LambdaContext) -> LambdaResult<O> + Send + 'a,
+    {
+        self.add(source.into(), None, f)
+    }
+
+    /// Calls `f` with the raw event when no route matches. Without a fallback, unmatched events
+    /// are an error.
+    pub fn fallback<F>(mut self, f: F) -> EventRouter<'a, O>
+    where
+        F: FnMut(Value, &LambdaContext) -> LambdaResult<O> + Send + 'a,
+    {
+        self.fallback = Some(Box::new(f));
+        self
+    }
+
+    fn add<D, F>(
+        mut self,
+        source: String,
+        detail_type: Option<String>,
+        mut f: F,
+    ) -> EventRouter<'a, O>
+    where
+        D: DeserializeOwned,
+        F: FnMut(EventBridgeEvent<D>, &LambdaContext) -> LambdaResult<O> + Send + 'a,
+    {
+        let route: Route<'a, O> = Box::new(move |event, context| {
+            let event = serde_json::from_value(event).map_err(|e| e.to_string())?;
+            f(event, context)
+        });
+        self.routes.push((source, detail_type, route));
+        self
+    }
+
+    /// Calls the function for `event`.
+    pub fn handle(&mut self, event: Value, context: LambdaContext) -> LambdaResult<O> {
+        let route = {
+            let source = event["source"].as_str();
+            let detail_type = event["detail-type"].as_str();
+            self.routes
+                .iter_mut()
+                .find(|&&mut (ref s, ref t, _)| {
+                    Some(s.as_str()) == source && (t.is_none() || t.as_deref() == detail_type)
+                })
+                .map(|&mut (_, _, ref mut route)| route)
+        };
+        match route.or(self.fallback.as_mut()) {
+            Some(route) => route(event, &context),
+            None => Err(format!(
+                "no route for source {} and detail-type {}",
+                event["source"], event["detail-type"]
+            ).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use LambdaContext;
+
+    fn event(source: &str, detail_type: &str, detail: Value) -> Value {
+        json!({
+            "version": "0",
+            "id": "89d1a02d-5ec7-412e-82f5-13505f849b41",
+            "detail-type": detail_type,
+            "source": source,
+            "account": "123456789012",
+            "time": "2016-12-30T18:44:49Z",
+            "region": "us-east-1",
+            "resources": ["arn:aws:events:us-east-1:123456789012:rule/SampleRule"],
+            "detail": detail
+        })
+    }
+
+    #[derive(Deserialize)]
+    struct Order {
+        id: u32,
+    }
+
+    fn router<'a>() -> EventRouter<'a, String> {
+        EventRouter::new()
+            .route("shop", "Order Placed", |event: EventBridgeEvent<Order>, _| {
+                Ok(format!("placed {}", event.detail.id))
+            })
+            .route_source("shop", |event: EventBridgeEvent, _| {
+                Ok(format!("shop {}", event.detail_type))
+            })
+            .route_source("aws.events", |event: ScheduledEvent, _| {
+                Ok(format!("scheduled by {}", event.resources[0]))
+            })
+    }
+
+    #[test]
+    fn routes() {
+        let placed = event("shop", "Order Placed", json!({"id": 7}));
+        assert_eq!(router().handle(placed, LambdaContext::fake()).unwrap(), "placed 7");
+
+        let shipped = event("shop", "Order Shipped", json!({}));
+        assert_eq!(router().handle(shipped, LambdaContext::fake()).unwrap(), "shop Order Shipped");
+
+        let scheduled = event("aws.events", "Scheduled Event", json!({}));
+        assert_eq!(
+            router().handle(scheduled, LambdaContext::fake()).unwrap(),
+            "scheduled by arn:aws:events:us-east-1:123456789012:rule/SampleRule"
+        );
+    }
+
+    #[test]
+    fn unmatched() {
+        let other = event("aws.ec2", "EC2 Instance State-change Notification", json!({}));
+        assert!(router().handle(other.clone(), LambdaContext::fake()).is_err());
+        let mut fallback = router().fallback(|event, _| Ok(event["source"].to_string()));
+        assert_eq!(fallback.handle(other, LambdaContext::fake()).unwrap(), "\"aws.ec2\"");
+    }
+
+    #[test]
+    fn reused() {
+        let mut count = 0;
+        let mut router = EventRouter::new().route_source("shop", move |_: EventBridgeEvent, _| {
+            count += 1;
+            Ok(count)
+        });
+        let placed = event("shop", "Order Placed", json!({"id": 7}));
+        assert_eq!(router.handle(placed.clone(), LambdaContext::fake()).unwrap(), 1);
+        assert_eq!(router.handle(placed, LambdaContext::fake()).unwrap(), 2);
+    }
+
+    #[test]
+    fn invalid_detail() {
+        let placed = event("shop", "Order Placed", json!({"id": "seven"}));
+        assert!(router().handle(placed, LambdaContext::fake()).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Kinesis Data Firehose data transformation events.
+//!
+//! Firehose invokes a [transformation function]
+//! (https://docs.aws.amazon.com/firehose/latest/dev/data-transformation.html) with a batch of
+//! records and expects every record back, marked as transformed, dropped or failed. The
+//! `transform` method builds that response from a closure that handles one record at a time:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::firehose::FirehoseEvent;
+//!
+//! lambda!(|event: FirehoseEvent, _context| {
+//!     Ok(event.transform(|data| {
+//!         String::from_utf8(data).map(|line| {
+//!             if line.trim().is_empty() {
+//!                 // drop blank lines
+//!                 None
+//!             } else {
+//!                 Some(line.to_uppercase().into_bytes())
+//!             }
+//!         })
+//!     }))
+//! });
+//! # }
+//! ```
+
+use std::fmt::Debug;
+
+/// The event a Lambda function receives from a Firehose delivery stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseEvent {
+    pub invocation_id: String,
+    pub delivery_stream_arn: String,
+    /// Set when the delivery stream reads from a Kinesis data stream.
+    #[serde(default)]
+    pub source_kinesis_stream_arn: Option<String>,
+    pub region: String,
+    pub records: Vec<FirehoseRecord>,
+}
+
+/// A record to be transformed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseRecord {
+    pub record_id: String,
+    /// Time the record was received by Firehose, in milliseconds since the Unix epoch.
+    pub approximate_arrival_timestamp: i64,
+    /// The decoded record data.
+    #[serde(with = "super::base64_bytes")]
+    pub data: Vec<u8>,
+    /// Set when the delivery stream reads from a Kinesis data stream.
+    #[serde(default)]
+    pub kinesis_record_metadata: Option<KinesisRecordMetadata>,
+}
+
+/// Information about the Kinesis data stream record a Firehose record was read from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KinesisRecordMetadata {
+    pub shard_id: String,
+    pub partition_key: String,
+    pub approximate_arrival_timestamp: i64,
+    pub sequence_number: String,
+    pub subsequence_number: u64,
+}
+
+/// The response a transformation function returns to Firehose.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FirehoseResponse {
+    pub records: Vec<FirehoseResponseRecord>,
+}
+
+/// The transformed version of a `FirehoseRecord`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseResponseRecord {
+    /// Must match the `record_id` of the record this was transformed from.
+    pub record_id: String,
+    pub result: FirehoseResult,
+    /// The transformed record data.
+    #[serde(with = "super::base64_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Status of a transformed record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FirehoseResult {
+    /// The record was transformed successfully.
+    Ok,
+    /// The record was dropped intentionally by the transformation function.
+    Dropped,
+    /// The record could not be transformed. Firehose writes it to the processing failure
+    /// destination.
+    ProcessingFailed,
+}
+
+impl FirehoseEvent {
+    /// Transforms every record with `f` and builds the response for Firehose.
+    ///
+    /// `f` receives the decoded data of each record. Returning `Ok(Some(data))` marks the record
+    /// `Ok` with the new data, `Ok(None)` marks it `Dropped`, and `Err(_)` marks it
+    /// `ProcessingFailed`. Dropped and failed records keep their original data.
+    pub fn transform<F, E>(self, f: F) -> FirehoseResponse
+    where
+        F: Fn(Vec<u8>) -> Result<Option<Vec<u8>>, E>,
+        E: Debug,
+    {
+        let records = self.records
+            .into_iter()
+            .map(|record| {
+                let (result, data) = match f(record.data.clone()) {
+                    Ok(Some(data)) => (FirehoseResult::Ok, data),
+                    Ok(None) => (FirehoseResult::Dropped, record.data),
+                    Err(e) => {
+                        warn!("failed to transform record {}: {:?}", record.record_id, e);
+                        (FirehoseResult::ProcessingFailed, record.data)
+                    }
+                };
+                FirehoseResponseRecord {
+                    record_id: record.record_id,
+                    result: result,
+                    data: data,
+                }
+            })
+            .collect();
+        FirehoseResponse { records: records }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn transform() {
+        let event: FirehoseEvent = serde_json::from_value(json!({
+            "invocationId": "invocation",
+            "deliveryStreamArn": "arn:aws:firehose:us-east-1:123456789012:deliverystream/test",
+            "region": "us-east-1",
+            "records": [
+                {"recordId": "1", "approximateArrivalTimestamp": 1, "data": "aGVsbG8="},
+                {"recordId": "2", "approximateArrivalTimestamp": 2, "data": "IA=="},
+                {"recordId": "3", "approximateArrivalTimestamp": 3, "data": "/w=="}
+            ]
+        })).unwrap();
+        let response = event.transform(|data| {
+            String::from_utf8(data).map(|line| {
+                if line.trim().is_empty() {
+                    None
+                } else {
+                    Some(line.to_uppercase().into_bytes())
+                }
+            })
+        });
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "records": [
+                    {"recordId": "1", "result": "Ok", "data": "SEVMTE8="},
+                    {"recordId": "2", "result": "Dropped", "data": "IA=="},
+                    {"recordId": "3", "result": "ProcessingFailed", "data": "/w=="}
+                ]
+            })
+        );
+    }
+}
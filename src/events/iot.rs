@@ -0,0 +1,87 @@
+//! AWS IoT rule invocations.
+//!
+//! An [IoT rule](https://docs.aws.amazon.com/iot/latest/developerguide/iot-sql-reference.html)
+//! invokes its Lambda action with whatever the rule's `SELECT` statement produces, so there is no
+//! fixed event format. `IotRuleEvent` reads the message payload into any type, along with the
+//! topic, client ID and timestamp if the rule selects them with these names:
+//!
+//! ```sql
+//! SELECT *, topic() AS topic, clientid() AS clientId, timestamp() AS timestamp
+//! FROM 'sensors/+/temperature'
+//! ```
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use crowbar::events::iot::IotRuleEvent;
+//!
+//! #[derive(Deserialize)]
+//! struct Reading {
+//!     celsius: f64,
+//! }
+//!
+//! lambda!(|event: IotRuleEvent<Reading>, _context| {
+//!     let sensor = event.topic_level(1).unwrap_or("unknown");
+//!     println!("{} reads {} °C", sensor, event.payload.celsius);
+//!     Ok(())
+//! });
+//! # }
+//! ```
+
+/// A message selected by an IoT rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IotRuleEvent<P> {
+    /// The topic the message was published to, selected as `topic() AS topic`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// The client that published the message, selected as `clientid() AS clientId`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// When the rule engine received the message in milliseconds since the Unix epoch, selected
+    /// as `timestamp() AS timestamp`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// The rest of the selected fields.
+    #[serde(flatten)]
+    pub payload: P,
+}
+
+impl<P> IotRuleEvent<P> {
+    /// A level of the topic, starting from zero, like the `topic(n)` SQL function (which starts
+    /// from one).
+    pub fn topic_level(&self, level: usize) -> Option<&str> {
+        self.topic
+            .as_ref()
+            .and_then(|topic| topic.split('/').nth(level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{self, Value};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Reading {
+        celsius: f64,
+    }
+
+    #[test]
+    fn payload() {
+        let event: IotRuleEvent<Reading> = serde_json::from_value(json!({
+            "celsius": 21.5,
+            "topic": "sensors/kitchen/temperature",
+            "clientId": "kitchen",
+            "timestamp": 1_546_433_107_000i64
+        })).unwrap();
+        assert_eq!(event.payload, Reading { celsius: 21.5 });
+        assert_eq!(event.topic_level(1), Some("kitchen"));
+        assert_eq!(event.client_id, Some("kitchen".to_string()));
+
+        let bare: IotRuleEvent<Value> = serde_json::from_value(json!({"celsius": 21.5})).unwrap();
+        assert_eq!(bare.topic, None);
+        assert_eq!(bare.payload, json!({"celsius": 21.5}));
+    }
+}
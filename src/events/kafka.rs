@@ -0,0 +1,314 @@
+//! Amazon MSK and self-managed Apache Kafka events.
+//!
+//! A [Kafka event source mapping](https://docs.aws.amazon.com/lambda/latest/dg/with-msk.html)
+//! invokes the function with batches of records, grouped by topic and partition. crowbar decodes
+//! the base64 keys and values, and `KafkaEvent::records` iterates over every record in order:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::kafka::KafkaEvent;
+//!
+//! lambda!(|event: KafkaEvent, _context| {
+//!     for record in event.records() {
+//!         let value = String::from_utf8_lossy(record.value.as_ref().map_or(&[][..], |v| &v[..]));
+//!         println!("{}-{}@{}: {}", record.topic, record.partition, record.offset, value);
+//!     }
+//!     Ok(())
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+/// The event a Lambda function receives from a Kafka event source mapping.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KafkaEvent {
+    /// `aws:kafka` for Amazon MSK, or `SelfManagedKafka`.
+    pub event_source: String,
+    /// The ARN of the MSK cluster.
+    #[serde(default)]
+    pub event_source_arn: Option<String>,
+    #[serde(default)]
+    pub bootstrap_servers: Option<String>,
+    /// The records of each partition, keyed by `topic-partition`.
+    pub records: HashMap<String, Vec<KafkaRecord>>,
+}
+
+impl KafkaEvent {
+    /// Every record in the event, ordered by topic, then partition, then offset.
+    pub fn records(&self) -> vec::IntoIter<&KafkaRecord> {
+        let mut records: Vec<_> = self.records.values().flatten().collect();
+        records.sort_by(|a, b| a.position().cmp(&b.position()));
+        records.into_iter()
+    }
+}
+
+impl IntoIterator for KafkaEvent {
+    type Item = KafkaRecord;
+    type IntoIter = vec::IntoIter<KafkaRecord>;
+
+    /// Every record in the event, in the same order as `records`.
+    fn into_iter(self) -> vec::IntoIter<KafkaRecord> {
+        let mut records: Vec<_> = self
+            .records
+            .into_values()
+            .flatten()
+            .collect();
+        records.sort_by(|a, b| a.position().cmp(&b.position()));
+        records.into_iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KafkaRecord {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    /// Milliseconds since the Unix epoch. What this time means depends on `timestamp_type`.
+    pub timestamp: i64,
+    pub timestamp_type: TimestampType,
+    #[serde(default, with = "super::base64_bytes::option")]
+    pub key: Option<Vec<u8>>,
+    #[serde(default, with = "super::base64_bytes::option")]
+    pub value: Option<Vec<u8>>,
+    #[serde(default)]
+    pub headers: Headers,
+}
+
+impl KafkaRecord {
+    fn position(&self) -> (&str, i32, i64) {
+        (&self.topic, self.partition, self.offset)
+    }
+
+    /// The timestamp as a `SystemTime`, or `None` if the record has no timestamp.
+    pub fn time(&self) -> Option<SystemTime> {
+        match self.timestamp_type {
+            TimestampType::NoTimestampType => None,
+            _ if self.timestamp < 0 => None,
+            _ => Some(UNIX_EPOCH + Duration::from_millis(self.timestamp as u64)),
+        }
+    }
+}
+
+/// What a record's timestamp means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimestampType {
+    /// When the producer created the record.
+    CreateTime,
+    /// When the broker appended the record to the log.
+    LogAppendTime,
+    NoTimestampType,
+}
+
+/// The headers of a record, in order. Kafka allows more than one header with the same key.
+///
+/// Lambda delivers headers as a list of single-entry objects with the value as an array of Java's
+/// signed bytes, such as `[{"source": [97, 112, 112]}]`, where bytes from 0x80 up are negative.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers(pub Vec<(String, Vec<u8>)>);
+
+impl Headers {
+    /// The value of the first header with `key`.
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| &value[..])
+    }
+
+    pub fn iter<'a>(&'a self) -> ::std::slice::Iter<'a, (String, Vec<u8>)> {
+        self.0.iter()
+    }
+}
+
+impl Serialize for Headers {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        struct Entry<'a>(&'a str, &'a [u8]);
+
+        impl<'a> Serialize for Entry<'a> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                let signed: Vec<i8> = self.1.iter().map(|&byte| byte as i8).collect();
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(self.0, &signed)?;
+                map.end()
+            }
+        }
+
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            seq.serialize_element(&Entry(key, value))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Headers {
+    fn deserialize<D>(deserializer: D) -> Result<Headers, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct HeadersVisitor;
+
+        impl<'de> Visitor<'de> for HeadersVisitor {
+            type Value = Headers;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a list of header objects")
+            }
+
+            fn visit_unit<E>(self) -> Result<Headers, E> {
+                Ok(Headers::default())
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Headers, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut headers = vec![];
+                while let Some(entries) = seq.next_element::<Entries>()? {
+                    headers.extend(entries.0);
+                }
+                Ok(Headers(headers))
+            }
+        }
+
+        struct Entries(Vec<(String, Vec<u8>)>);
+
+        impl<'de> Deserialize<'de> for Entries {
+            fn deserialize<D>(deserializer: D) -> Result<Entries, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct EntriesVisitor;
+
+                impl<'de> Visitor<'de> for EntriesVisitor {
+                    type Value = Entries;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "a header object")
+                    }
+
+                    fn visit_map<A>(self, mut map: A) -> Result<Entries, A::Error>
+                    where
+                        A: MapAccess<'de>,
+                    {
+                        let mut entries = vec![];
+                        while let Some((key, value)) = map.next_entry::<String, Vec<i8>>()? {
+                            entries.push((key, value.into_iter().map(|byte| byte as u8).collect()));
+                        }
+                        Ok(Entries(entries))
+                    }
+                }
+
+                deserializer.deserialize_map(EntriesVisitor)
+            }
+        }
+
+        deserializer.deserialize_any(HeadersVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn record(topic: &str, partition: i32, offset: i64) -> serde_json::Value {
+        json!({
+            "topic": topic,
+            "partition": partition,
+            "offset": offset,
+            "timestamp": 1_545_084_650_987i64,
+            "timestampType": "CREATE_TIME",
+            "key": null,
+            "value": "SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==",
+            "headers": []
+        })
+    }
+
+    #[test]
+    fn deserialize() {
+        let event: KafkaEvent = serde_json::from_value(json!({
+            "eventSource": "aws:kafka",
+            "eventSourceArn": "arn:aws:kafka:us-east-1:123456789012:cluster/demo-cluster-1/751d2973",
+            "bootstrapServers": "b-2.demo-cluster-1.a1bcde.c1.kafka.us-east-1.amazonaws.com:9092",
+            "records": {
+                "mytopic-0": [{
+                    "topic": "mytopic",
+                    "partition": 0,
+                    "offset": 15,
+                    "timestamp": 1_545_084_650_987i64,
+                    "timestampType": "LOG_APPEND_TIME",
+                    "key": "a2V5",
+                    "value": "SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==",
+                    "headers": [
+                        {"source": [97, 112, 112]},
+                        {"trace": [49]},
+                        {"binary": [-1, 97]}
+                    ]
+                }]
+            }
+        })).unwrap();
+        let record = event.records().next().unwrap();
+        assert_eq!(record.key, Some(b"key".to_vec()));
+        assert_eq!(record.value, Some(b"Hello, this is a test.".to_vec()));
+        assert_eq!(record.headers.get("source"), Some(&b"app"[..]));
+        assert_eq!(record.headers.get("binary"), Some(&[0xff, b'a'][..]));
+        assert_eq!(record.timestamp_type, TimestampType::LogAppendTime);
+        assert_eq!(
+            record.time(),
+            Some(UNIX_EPOCH + Duration::from_millis(1_545_084_650_987))
+        );
+
+        let serialized = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            serialized["records"]["mytopic-0"][0]["headers"][2],
+            json!({"binary": [-1, 97]})
+        );
+        let round_trip: KafkaEvent = serde_json::from_value(serialized).unwrap();
+        assert_eq!(round_trip, event);
+    }
+
+    #[test]
+    fn ordered() {
+        let event: KafkaEvent = serde_json::from_value(json!({
+            "eventSource": "SelfManagedKafka",
+            "records": {
+                "orders-1": [record("orders", 1, 7), record("orders", 1, 8)],
+                "orders-0": [record("orders", 0, 4), record("orders", 0, 3)],
+                "audit-0": [record("audit", 0, 9)]
+            }
+        })).unwrap();
+        let positions: Vec<_> = event
+            .records()
+            .map(|r| (r.topic.clone(), r.partition, r.offset))
+            .collect();
+        let expected = vec![
+            ("audit".to_string(), 0, 9),
+            ("orders".to_string(), 0, 3),
+            ("orders".to_string(), 0, 4),
+            ("orders".to_string(), 1, 7),
+            ("orders".to_string(), 1, 8),
+        ];
+        assert_eq!(positions, expected);
+
+        let owned: Vec<_> = event.into_iter().map(|r| r.offset).collect();
+        assert_eq!(owned, vec![9, 3, 4, 7, 8]);
+    }
+}
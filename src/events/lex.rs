@@ -0,0 +1,332 @@
+//! Amazon Lex (V1) code hook events and dialog actions.
+//!
+//! Lex invokes the [code hooks](https://docs.aws.amazon.com/lex/latest/dg/lambda-input-response-format.html)
+//! of an intent to validate user input while eliciting slots (`DialogCodeHook`) and to fulfill
+//! the intent (`FulfillmentCodeHook`). The response tells Lex what to do next:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::lex::{FulfillmentState, InvocationSource, LexEvent, LexResponse};
+//!
+//! lambda!(|event: LexEvent, _context| {
+//!     let intent = &event.current_intent;
+//!     Ok(match event.invocation_source {
+//!         InvocationSource::DialogCodeHook => match intent.slot("Flavor") {
+//!             Some("pineapple") => LexResponse::elicit_slot(
+//!                 &intent.name,
+//!                 intent.slots.clone(),
+//!                 "Flavor",
+//!                 "We're out of pineapple. Which other flavor would you like?",
+//!             ),
+//!             _ => LexResponse::delegate(intent.slots.clone()),
+//!         },
+//!         InvocationSource::FulfillmentCodeHook => LexResponse::close(
+//!             FulfillmentState::Fulfilled,
+//!             "Your order is on its way.",
+//!         ),
+//!     }.with_session_attributes(event.session_attributes.clone()))
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+/// The event a Lambda function receives from a Lex code hook.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LexEvent {
+    pub current_intent: CurrentIntent,
+    pub bot: Bot,
+    pub user_id: String,
+    /// The text the user typed, or the transcription of what they said.
+    pub input_transcript: String,
+    pub invocation_source: InvocationSource,
+    /// `Text` or `Voice`.
+    pub output_dialog_mode: String,
+    pub message_version: String,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub session_attributes: HashMap<String, String>,
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub request_attributes: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentIntent {
+    pub name: String,
+    /// Slot values, with `None` for slots the user hasn't provided yet.
+    #[serde(default, deserialize_with = "super::nullable_default")]
+    pub slots: HashMap<String, Option<String>>,
+    #[serde(default)]
+    pub slot_details: Value,
+    pub confirmation_status: ConfirmationStatus,
+}
+
+impl CurrentIntent {
+    /// The value of a slot, if the user provided one.
+    pub fn slot(&self, name: &str) -> Option<&str> {
+        self.slots
+            .get(name)
+            .and_then(|value| value.as_ref())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bot {
+    pub name: String,
+    pub alias: Option<String>,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvocationSource {
+    DialogCodeHook,
+    FulfillmentCodeHook,
+}
+
+/// Whether the user confirmed the intent, for intents with a confirmation prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConfirmationStatus {
+    None,
+    Confirmed,
+    Denied,
+}
+
+/// The response a Lambda function returns to Lex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LexResponse {
+    /// Replaces the session attributes. Lex clears them if they aren't returned.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub session_attributes: HashMap<String, String>,
+    pub dialog_action: DialogAction,
+}
+
+/// What Lex does after the code hook returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum DialogAction {
+    /// Ends the conversation.
+    #[serde(rename_all = "camelCase")]
+    Close {
+        fulfillment_state: FulfillmentState,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<Message>,
+    },
+    /// Asks the user which intent they want.
+    #[serde(rename_all = "camelCase")]
+    ElicitIntent {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<Message>,
+    },
+    /// Asks the user for the value of a slot.
+    #[serde(rename_all = "camelCase")]
+    ElicitSlot {
+        intent_name: String,
+        slots: HashMap<String, Option<String>>,
+        slot_to_elicit: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<Message>,
+    },
+    /// Asks the user to confirm the intent.
+    #[serde(rename_all = "camelCase")]
+    ConfirmIntent {
+        intent_name: String,
+        slots: HashMap<String, Option<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<Message>,
+    },
+    /// Lets Lex choose the next action from the bot configuration.
+    #[serde(rename_all = "camelCase")]
+    Delegate { slots: HashMap<String, Option<String>> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FulfillmentState {
+    Fulfilled,
+    Failed,
+}
+
+/// A message for Lex to send to the user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Message {
+    /// `PlainText`, `SSML` or `CustomPayload`.
+    pub content_type: String,
+    pub content: String,
+}
+
+impl Message {
+    pub fn plain_text<S>(content: S) -> Message
+    where
+        S: Into<String>,
+    {
+        Message {
+            content_type: "PlainText".to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn ssml<S>(content: S) -> Message
+    where
+        S: Into<String>,
+    {
+        Message {
+            content_type: "SSML".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+impl From<String> for Message {
+    fn from(content: String) -> Message {
+        Message::plain_text(content)
+    }
+}
+
+impl<'a> From<&'a str> for Message {
+    fn from(content: &'a str) -> Message {
+        Message::plain_text(content)
+    }
+}
+
+impl LexResponse {
+    fn new(dialog_action: DialogAction) -> LexResponse {
+        LexResponse {
+            session_attributes: HashMap::new(),
+            dialog_action: dialog_action,
+        }
+    }
+
+    /// Ends the conversation with `message`.
+    pub fn close<M>(fulfillment_state: FulfillmentState, message: M) -> LexResponse
+    where
+        M: Into<Message>,
+    {
+        LexResponse::new(DialogAction::Close {
+            fulfillment_state: fulfillment_state,
+            message: Some(message.into()),
+        })
+    }
+
+    /// Asks the user which intent they want with `message`.
+    pub fn elicit_intent<M>(message: M) -> LexResponse
+    where
+        M: Into<Message>,
+    {
+        LexResponse::new(DialogAction::ElicitIntent {
+            message: Some(message.into()),
+        })
+    }
+
+    /// Asks the user for the value of `slot_to_elicit` with `message`.
+    pub fn elicit_slot<N, S, M>(
+        intent_name: N,
+        slots: HashMap<String, Option<String>>,
+        slot_to_elicit: S,
+        message: M,
+    ) -> LexResponse
+    where
+        N: Into<String>,
+        S: Into<String>,
+        M: Into<Message>,
+    {
+        LexResponse::new(DialogAction::ElicitSlot {
+            intent_name: intent_name.into(),
+            slots: slots,
+            slot_to_elicit: slot_to_elicit.into(),
+            message: Some(message.into()),
+        })
+    }
+
+    /// Asks the user to confirm the intent with `message`.
+    pub fn confirm_intent<N, M>(
+        intent_name: N,
+        slots: HashMap<String, Option<String>>,
+        message: M,
+    ) -> LexResponse
+    where
+        N: Into<String>,
+        M: Into<Message>,
+    {
+        LexResponse::new(DialogAction::ConfirmIntent {
+            intent_name: intent_name.into(),
+            slots: slots,
+            message: Some(message.into()),
+        })
+    }
+
+    /// Lets Lex choose the next action, with the (possibly corrected) `slots`.
+    pub fn delegate(slots: HashMap<String, Option<String>>) -> LexResponse {
+        LexResponse::new(DialogAction::Delegate { slots: slots })
+    }
+
+    /// Sets the session attributes, which Lex passes to the next code hook invocation.
+    pub fn with_session_attributes(mut self, attributes: HashMap<String, String>) -> LexResponse {
+        self.session_attributes = attributes;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn event() {
+        let event: LexEvent = serde_json::from_value(json!({
+            "currentIntent": {
+                "name": "OrderIceCream",
+                "slots": {"Flavor": "vanilla", "Size": null},
+                "confirmationStatus": "None"
+            },
+            "bot": {"name": "IceCream", "alias": "$LATEST", "version": "$LATEST"},
+            "userId": "user",
+            "inputTranscript": "vanilla please",
+            "invocationSource": "DialogCodeHook",
+            "outputDialogMode": "Text",
+            "messageVersion": "1.0",
+            "sessionAttributes": {},
+            "requestAttributes": null
+        })).unwrap();
+        assert_eq!(event.current_intent.slot("Flavor"), Some("vanilla"));
+        assert_eq!(event.current_intent.slot("Size"), None);
+        assert_eq!(event.invocation_source, InvocationSource::DialogCodeHook);
+    }
+
+    #[test]
+    fn dialog_actions() {
+        let mut slots = HashMap::new();
+        slots.insert("Size".to_string(), None);
+        let response = LexResponse::elicit_slot("OrderIceCream", slots, "Size", "Which size?");
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "dialogAction": {
+                    "type": "ElicitSlot",
+                    "intentName": "OrderIceCream",
+                    "slots": {"Size": null},
+                    "slotToElicit": "Size",
+                    "message": {"contentType": "PlainText", "content": "Which size?"}
+                }
+            })
+        );
+
+        let response = LexResponse::close(FulfillmentState::Fulfilled, Message::ssml("<speak>Done</speak>"));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "dialogAction": {
+                    "type": "Close",
+                    "fulfillmentState": "Fulfilled",
+                    "message": {"contentType": "SSML", "content": "<speak>Done</speak>"}
+                }
+            })
+        );
+    }
+}
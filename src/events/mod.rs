@@ -0,0 +1,101 @@
+//! Typed events for AWS services that invoke Lambda functions.
+//!
+//! Every event in this module implements serde's `Deserialize`, so it can be declared directly as
+//! the event argument of a handler in place of `Value`:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::{LambdaContext, LambdaResult};
+//! use crowbar::events::cloudwatch_logs::CloudWatchLogsEvent;
+//!
+//! fn count_lines(event: CloudWatchLogsEvent, _: LambdaContext) -> LambdaResult<usize> {
+//!     Ok(event.aws_logs.data.log_events.len())
+//! }
+//!
+//! lambda!(count_lines);
+//! # }
+//! ```
+//!
+//! Functions with more than one trigger can take an `AnyEvent`, which detects where the event
+//! came from.
+
+pub mod alb;
+pub mod alexa;
+pub mod appsync;
+pub mod authorizer;
+pub mod cloudwatch_logs;
+pub mod codecommit;
+pub mod cognito;
+pub mod eventbridge;
+pub mod firehose;
+pub mod iot;
+pub mod kafka;
+pub mod lex;
+pub mod ses;
+pub mod sns;
+pub mod sqs;
+
+mod any;
+pub use self::any::AnyEvent;
+
+use serde::de::{Deserialize, Deserializer};
+
+/// Deserializes a `null` as the default value, for fields that AWS sends as `null` when empty.
+fn nullable_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+/// serde adapter for byte fields that AWS delivers as base64 strings, for use with
+/// `#[serde(with = "...")]`.
+mod base64_bytes {
+    use base64;
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(&encoded).map_err(de::Error::custom)
+    }
+
+    /// The same, for optional fields that are missing or `null` when empty.
+    pub mod option {
+        use base64;
+        use serde::de::{self, Deserialize, Deserializer};
+        use serde::ser::Serializer;
+
+        pub fn serialize<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match *bytes {
+                Some(ref bytes) => super::serialize(bytes, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(encoded) => base64::decode(&encoded).map(Some).map_err(de::Error::custom),
+                None => Ok(None),
+            }
+        }
+    }
+}
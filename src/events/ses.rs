@@ -0,0 +1,254 @@
+//! Amazon SES receipt rule events.
+//!
+//! A receipt rule with a [Lambda action]
+//! (https://docs.aws.amazon.com/ses/latest/DeveloperGuide/receiving-email-action-lambda.html)
+//! invokes the function with the headers of an incoming email and the verdicts of SES's checks.
+//! When the action is invoked synchronously (`RequestResponse`), the function can return a
+//! `Disposition` to stop the remaining actions:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::events::ses::{Disposition, SesEvent};
+//!
+//! lambda!(|event: SesEvent, _context| {
+//!     let receipt = &event.records[0].ses.receipt;
+//!     if receipt.spam_verdict.failed() || receipt.virus_verdict.failed() {
+//!         Ok(Disposition::StopRuleSet)
+//!     } else {
+//!         Ok(Disposition::Continue)
+//!     }
+//! });
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+/// The event a Lambda function receives from an SES receipt rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SesEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<SesRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SesRecord {
+    /// Always `aws:ses`.
+    pub event_source: String,
+    pub event_version: String,
+    pub ses: SesMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SesMessage {
+    pub mail: Mail,
+    pub receipt: Receipt,
+}
+
+/// The email, without its body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mail {
+    /// When the email was received, in ISO 8601 format.
+    pub timestamp: String,
+    /// The envelope `MAIL FROM` address.
+    pub source: String,
+    /// The ID SES assigned to the email, which is also the S3 object key used by S3 actions.
+    pub message_id: String,
+    /// The envelope `RCPT TO` addresses.
+    pub destination: Vec<String>,
+    /// Whether `headers` was truncated because the headers are larger than 10 KB.
+    pub headers_truncated: bool,
+    /// Every header of the email, in order.
+    #[serde(default)]
+    pub headers: Vec<Header>,
+    pub common_headers: CommonHeaders,
+}
+
+impl Mail {
+    /// The values of every header called `name`, ignoring case.
+    pub fn header<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+/// The headers SES parses from every email.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommonHeaders {
+    #[serde(default)]
+    pub return_path: Option<String>,
+    #[serde(default)]
+    pub from: Vec<String>,
+    #[serde(default)]
+    pub date: Option<String>,
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
+    #[serde(default)]
+    pub sender: Vec<String>,
+    #[serde(default)]
+    pub reply_to: Vec<String>,
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+}
+
+/// How SES received the email.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+    pub timestamp: String,
+    pub processing_time_millis: u64,
+    /// The recipients the receipt rule matched.
+    pub recipients: Vec<String>,
+    pub spam_verdict: Verdict,
+    pub virus_verdict: Verdict,
+    pub spf_verdict: Verdict,
+    pub dkim_verdict: Verdict,
+    pub dmarc_verdict: Verdict,
+    /// The DMARC policy of the sender's domain (`none`, `quarantine` or `reject`), if DMARC
+    /// failed.
+    #[serde(default)]
+    pub dmarc_policy: Option<String>,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Verdict {
+    pub status: VerdictStatus,
+}
+
+impl Verdict {
+    pub fn passed(&self) -> bool {
+        self.status == VerdictStatus::Pass
+    }
+
+    pub fn failed(&self) -> bool {
+        self.status == VerdictStatus::Fail
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VerdictStatus {
+    Pass,
+    Fail,
+    /// SES couldn't tell whether the email passed.
+    Gray,
+    ProcessingFailed,
+    /// The check is turned off for the receipt rule.
+    Disabled,
+}
+
+/// The action that invoked the function.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Action {
+    /// Always `Lambda`.
+    #[serde(rename = "type")]
+    pub action_type: String,
+    pub function_arn: String,
+    /// `RequestResponse` if SES waits for a `Disposition`, or `Event`.
+    pub invocation_type: String,
+    /// Other fields, for forward compatibility.
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+
+/// What SES does with the rest of the receipt rules after a synchronous Lambda action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "disposition", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Disposition {
+    /// Runs the remaining actions.
+    Continue,
+    /// Skips the remaining actions of this rule.
+    StopRule,
+    /// Skips the remaining actions and rules.
+    StopRuleSet,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn deserialize() {
+        let event: SesEvent = serde_json::from_value(json!({
+            "Records": [{
+                "eventSource": "aws:ses",
+                "eventVersion": "1.0",
+                "ses": {
+                    "mail": {
+                        "timestamp": "2019-08-05T21:30:02.028Z",
+                        "source": "prvs=144d0cba7=sender@example.com",
+                        "messageId": "EXAMPLE7c191be45-e9aedb9a-02f9-4d12-a87d-dd0099a07f8a-000000",
+                        "destination": ["recipient@example.com"],
+                        "headersTruncated": false,
+                        "headers": [
+                            {"name": "Return-Path", "value": "<sender@example.com>"},
+                            {"name": "Received", "value": "from mx1"},
+                            {"name": "received", "value": "from mx2"}
+                        ],
+                        "commonHeaders": {
+                            "returnPath": "sender@example.com",
+                            "from": ["Sender Name <sender@example.com>"],
+                            "date": "Mon, 5 Aug 2019 21:29:57 +0000",
+                            "to": ["recipient@example.com"],
+                            "messageId": "<0123456789abcdef@mail.example.com>",
+                            "subject": "This is a test"
+                        }
+                    },
+                    "receipt": {
+                        "timestamp": "2019-08-05T21:30:02.028Z",
+                        "processingTimeMillis": 1205,
+                        "recipients": ["recipient@example.com"],
+                        "spamVerdict": {"status": "PASS"},
+                        "virusVerdict": {"status": "PASS"},
+                        "spfVerdict": {"status": "PASS"},
+                        "dkimVerdict": {"status": "GRAY"},
+                        "dmarcVerdict": {"status": "FAIL"},
+                        "dmarcPolicy": "reject",
+                        "action": {
+                            "type": "Lambda",
+                            "functionArn": "arn:aws:lambda:us-east-1:123456789012:function:ses",
+                            "invocationType": "RequestResponse"
+                        }
+                    }
+                }
+            }]
+        })).unwrap();
+        let message = &event.records[0].ses;
+        let received: Vec<_> = message.mail.header("RECEIVED").collect();
+        assert_eq!(received, vec!["from mx1", "from mx2"]);
+        assert_eq!(message.mail.common_headers.subject, Some("This is a test".to_string()));
+        assert!(message.receipt.spam_verdict.passed());
+        assert_eq!(message.receipt.dkim_verdict.status, VerdictStatus::Gray);
+        assert!(message.receipt.dmarc_verdict.failed());
+    }
+
+    #[test]
+    fn disposition() {
+        assert_eq!(
+            serde_json::to_value(Disposition::StopRuleSet).unwrap(),
+            json!({"disposition": "STOP_RULE_SET"})
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Amazon SNS events.
+//!
+//! SNS invokes [subscribed functions](https://docs.aws.amazon.com/lambda/latest/dg/with-sns.html)
+//! with one notification per event.
+
+use std::collections::HashMap;
+
+/// The event a Lambda function receives from an SNS topic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnsEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<SnsRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsRecord {
+    pub event_version: String,
+    pub event_subscription_arn: String,
+    /// Always `aws:sns`.
+    pub event_source: String,
+    pub sns: SnsMessage,
+}
+
+/// A notification published to the topic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub message_type: String,
+    pub message_id: String,
+    pub topic_arn: String,
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub message: String,
+    /// When the notification was published, in ISO 8601 format.
+    pub timestamp: String,
+    pub signature_version: String,
+    pub signature: String,
+    #[serde(rename = "SigningCertUrl")]
+    pub signing_cert_url: String,
+    #[serde(rename = "UnsubscribeUrl")]
+    pub unsubscribe_url: String,
+    #[serde(default)]
+    pub message_attributes: HashMap<String, SnsMessageAttribute>,
+}
+
+/// A message attribute set by the publisher.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessageAttribute {
+    /// `String`, `String.Array`, `Number` or `Binary`.
+    #[serde(rename = "Type")]
+    pub data_type: String,
+    pub value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn deserialize() {
+        let event: SnsEvent = serde_json::from_value(json!({
+            "Records": [{
+                "EventVersion": "1.0",
+                "EventSubscriptionArn": "arn:aws:sns:us-east-1:123456789012:topic:subscription",
+                "EventSource": "aws:sns",
+                "Sns": {
+                    "SignatureVersion": "1",
+                    "Timestamp": "2019-01-02T12:45:07.000Z",
+                    "Signature": "tcc6faL2yUC6dgZdmrwh1Y4cGa/ebXEkAi6RibDsvpi+tE/1+82j...",
+                    "SigningCertUrl": "https://sns.us-east-1.amazonaws.com/cert.pem",
+                    "MessageId": "95df01b4-ee98-5cb9-9903-4c221d41eb5e",
+                    "Message": "hello",
+                    "MessageAttributes": {"Test": {"Type": "String", "Value": "TestString"}},
+                    "Type": "Notification",
+                    "UnsubscribeUrl": "https://sns.us-east-1.amazonaws.com/unsubscribe",
+                    "TopicArn": "arn:aws:sns:us-east-1:123456789012:topic",
+                    "Subject": null
+                }
+            }]
+        })).unwrap();
+        let message = &event.records[0].sns;
+        assert_eq!(message.message, "hello");
+        assert_eq!(message.subject, None);
+        assert_eq!(message.message_attributes["Test"].value, "TestString");
+    }
+}
@@ -0,0 +1,84 @@
+//! Amazon SQS events.
+//!
+//! Lambda polls [SQS queues](https://docs.aws.amazon.com/lambda/latest/dg/with-sqs.html) and
+//! invokes the function with a batch of messages. If the function returns an error, every message
+//! in the batch becomes visible in the queue again.
+
+use std::collections::HashMap;
+
+/// The event a Lambda function receives from an SQS queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SqsEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<SqsMessage>,
+}
+
+/// A message received from the queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsMessage {
+    pub message_id: String,
+    pub receipt_handle: String,
+    pub body: String,
+    /// System attributes such as `ApproximateReceiveCount` and `SentTimestamp`.
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+    #[serde(default)]
+    pub message_attributes: HashMap<String, SqsMessageAttribute>,
+    pub md5_of_body: String,
+    /// Always `aws:sqs`.
+    pub event_source: String,
+    #[serde(rename = "eventSourceARN")]
+    pub event_source_arn: String,
+    pub aws_region: String,
+}
+
+/// A message attribute set by the sender.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsMessageAttribute {
+    /// `String`, `Number` or `Binary`, optionally followed by a custom type.
+    pub data_type: String,
+    #[serde(default)]
+    pub string_value: Option<String>,
+    /// The base64-encoded value of a `Binary` attribute.
+    #[serde(default)]
+    pub binary_value: Option<String>,
+    #[serde(default)]
+    pub string_list_values: Vec<String>,
+    #[serde(default)]
+    pub binary_list_values: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn deserialize() {
+        let event: SqsEvent = serde_json::from_value(json!({
+            "Records": [{
+                "messageId": "059f36b4-87a3-44ab-83d2-661975830a7d",
+                "receiptHandle": "AQEBwJnKyrHigUMZj6rYigCgxlaS3SLy0a...",
+                "body": "hello",
+                "attributes": {"ApproximateReceiveCount": "1"},
+                "messageAttributes": {
+                    "priority": {"stringValue": "high", "stringListValues": [],
+                                 "binaryListValues": [], "dataType": "String"}
+                },
+                "md5OfBody": "5d41402abc4b2a76b9719d911017c592",
+                "eventSource": "aws:sqs",
+                "eventSourceARN": "arn:aws:sqs:us-east-1:123456789012:queue",
+                "awsRegion": "us-east-1"
+            }]
+        })).unwrap();
+        let message = &event.records[0];
+        assert_eq!(message.body, "hello");
+        assert_eq!(message.event_source_arn, "arn:aws:sqs:us-east-1:123456789012:queue");
+        assert_eq!(
+            message.message_attributes["priority"].string_value,
+            Some("high".to_string())
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Middleware around handlers.
+//!
+//! A `Layer` runs around the rest of a pipeline: it receives the event and context, can inspect or
+//! change them, and decides whether to run the rest of the pipeline with `Next::run` or to respond
+//! by itself. Wrap a handler with `pipeline` and add layers, outermost first. A pipeline is a
+//! `Handler`, so `lambda!` builds it once and it's reused for every invocation:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::layer::{guard, pipeline, Logging, Timing};
+//! use crowbar::{LambdaContext, LambdaResult};
+//! use crowbar::events::sqs::SqsEvent;
+//!
+//! fn handler(event: SqsEvent, _: LambdaContext) -> LambdaResult<usize> {
+//!     Ok(event.records.len())
+//! }
+//!
+//! lambda!(impl pipeline(handler)
+//!     .with_layer(Logging::new())
+//!     .with_layer(Timing)
+//!     .with_layer(guard(|event, _| match event["Records"].as_array() {
+//!         Some(records) if records.is_empty() => Some(Ok(0.into())),
+//!         Some(_) => None,
+//!         None => Some(Err("not an SQS event".into())),
+//!     })));
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+use std::time::Instant;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+
+use {Handler, LambdaContext, LambdaError, LambdaResult, Value};
+
+/// Middleware that runs around the rest of a pipeline.
+pub trait Layer {
+    /// Handles an invocation, usually by calling `next.run`. Returning without calling it skips the
+    /// rest of the pipeline, including the handler.
+    fn call<'n, 'a>(
+        &self,
+        event: Value,
+        context: LambdaContext<'a>,
+        next: Next<'n, 'a>,
+    ) -> LambdaResult<Value>;
+}
+
+/// The layers after the current one, and the handler.
+pub struct Next<'n, 'a> {
+    layers: &'n [Box<dyn Layer + Send>],
+    handler: Box<dyn FnOnce(Value, LambdaContext<'a>) -> LambdaResult<Value> + 'n>,
+}
+
+impl<'n, 'a> Next<'n, 'a> {
+    /// Runs the rest of the pipeline.
+    pub fn run(self, event: Value, context: LambdaContext<'a>) -> LambdaResult<Value> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => layer.call(
+                event,
+                context,
+                Next {
+                    layers: layers,
+                    handler: self.handler,
+                },
+            ),
+            None => (self.handler)(event, context),
+        }
+    }
+}
+
+/// Wraps a handler to add layers around it.
+pub fn pipeline<F, E, O>(f: F) -> Pipeline<F, E, O>
+where
+    F: FnMut(E, LambdaContext) -> LambdaResult<O>,
+{
+    Pipeline {
+        layers: Vec::new(),
+        f: f,
+        marker: PhantomData,
+    }
+}
+
+/// A handler with layers around it, created by `pipeline`.
+pub struct Pipeline<F, E, O> {
+    layers: Vec<Box<dyn Layer + Send>>,
+    f: F,
+    marker: PhantomData<fn(E) -> O>,
+}
+
+impl<F, E, O> Pipeline<F, E, O>
+where
+    F: FnMut(E, LambdaContext) -> LambdaResult<O>,
+    E: DeserializeOwned,
+    O: Serialize,
+{
+    /// Adds a layer inside the layers already added.
+    pub fn with_layer<L>(mut self, layer: L) -> Pipeline<F, E, O>
+    where
+        L: Layer + Send + 'static,
+    {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Runs the layers and the handler.
+    ///
+    /// Layers see the event and response as JSON; the event is deserialized just before the
+    /// handler runs.
+    pub fn handle(&mut self, event: Value, context: LambdaContext) -> LambdaResult<Value> {
+        let f = &mut self.f;
+        let next = Next {
+            layers: &self.layers,
+            handler: Box::new(move |event, context| {
+                let event = serde_json::from_value(event).map_err(|e| e.to_string())?;
+                let output = f(event, context)?;
+                Ok(serde_json::to_value(output).map_err(|e| e.to_string())?)
+            }),
+        };
+        next.run(event, context)
+    }
+}
+
+impl<F, E, O> Handler for Pipeline<F, E, O>
+where
+    F: FnMut(E, LambdaContext) -> LambdaResult<O>,
+    E: DeserializeOwned,
+    O: Serialize,
+{
+    type Event = Value;
+    type Output = Value;
+    type Error = LambdaError;
+
+    fn call(&mut self, event: Value, context: LambdaContext) -> LambdaResult<Value> {
+        self.handle(event, context)
+    }
+}
+
+/// Logs how long the rest of the pipeline took.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing;
+
+impl Layer for Timing {
+    fn call<'n, 'a>(
+        &self,
+        event: Value,
+        context: LambdaContext<'a>,
+        next: Next<'n, 'a>,
+    ) -> LambdaResult<Value> {
+        let request_id = context.aws_request_id().to_string();
+        let start = Instant::now();
+        let result = next.run(event, context);
+        let elapsed = start.elapsed();
+        info!("{} took {:.3} ms", request_id, elapsed.as_secs_f64() * 1000.0);
+        result
+    }
+}
+
+/// Logs the start and outcome of each invocation, and optionally the event and response.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logging {
+    events: bool,
+}
+
+impl Logging {
+    pub fn new() -> Logging {
+        Logging::default()
+    }
+
+    /// Also logs the event and the response at the `debug` level. These can contain sensitive
+    /// data.
+    pub fn with_events(self) -> Logging {
+        Logging { events: true }
+    }
+}
+
+impl Layer for Logging {
+    fn call<'n, 'a>(
+        &self,
+        event: Value,
+        context: LambdaContext<'a>,
+        next: Next<'n, 'a>,
+    ) -> LambdaResult<Value> {
+        let request_id = context.aws_request_id().to_string();
+        info!("{} started {}", request_id, context.function_name());
+        if self.events {
+            debug!("{} event: {}", request_id, event);
+        }
+        let result = next.run(event, context);
+        match result {
+            Ok(ref response) => {
+                if self.events {
+                    debug!("{} response: {}", request_id, response);
+                }
+                info!("{} succeeded", request_id);
+            }
+            Err(ref e) => warn!("{} failed: {}", request_id, e),
+        }
+        result
+    }
+}
+
+/// Creates a layer that responds with the result of `f` instead of running the rest of the
+/// pipeline when `f` returns `Some`, such as to reject invalid events or to answer repeated
+/// requests.
+pub fn guard<F>(f: F) -> Guard<F>
+where
+    F: Fn(&Value, &LambdaContext) -> Option<LambdaResult<Value>>,
+{
+    Guard(f)
+}
+
+/// A layer created by `guard`.
+pub struct Guard<F>(F);
+
+impl<F> Layer for Guard<F>
+where
+    F: Fn(&Value, &LambdaContext) -> Option<LambdaResult<Value>>,
+{
+    fn call<'n, 'a>(
+        &self,
+        event: Value,
+        context: LambdaContext<'a>,
+        next: Next<'n, 'a>,
+    ) -> LambdaResult<Value> {
+        match (self.0)(&event, &context) {
+            Some(response) => response,
+            None => next.run(event, context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Record(&'static str, Arc<Mutex<Vec<String>>>);
+
+    impl Layer for Record {
+        fn call<'n, 'a>(
+            &self,
+            event: Value,
+            context: LambdaContext<'a>,
+            next: Next<'n, 'a>,
+        ) -> LambdaResult<Value> {
+            self.1.lock().unwrap().push(format!("{} before", self.0));
+            let result = next.run(event, context);
+            self.1.lock().unwrap().push(format!("{} after", self.0));
+            result
+        }
+    }
+
+    #[test]
+    fn order() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let handler_calls = calls.clone();
+        let response = pipeline(move |event: Vec<u32>, _: LambdaContext| {
+            handler_calls.lock().unwrap().push("handler".to_string());
+            Ok(event.iter().sum::<u32>())
+        }).with_layer(Record("outer", calls.clone()))
+            .with_layer(Timing)
+            .with_layer(Logging::new().with_events())
+            .with_layer(Record("inner", calls.clone()))
+            .handle(json!([1, 2, 3]), LambdaContext::fake())
+            .unwrap();
+        assert_eq!(response, json!(6));
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "outer before",
+                "inner before",
+                "handler",
+                "inner after",
+                "outer after",
+            ]
+        );
+    }
+
+    #[test]
+    fn short_circuit() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let mut p = pipeline(|event: Value, _: LambdaContext| Ok(event))
+            .with_layer(guard(|event, _| {
+                if event.is_null() {
+                    Some(Err("missing event".into()))
+                } else if event["cached"].as_bool() == Some(true) {
+                    Some(Ok(json!("cached")))
+                } else {
+                    None
+                }
+            }))
+            .with_layer(Record("inner", calls.clone()));
+        assert!(p.handle(Value::Null, LambdaContext::fake()).is_err());
+        assert_eq!(
+            p.handle(json!({"cached": true}), LambdaContext::fake()).unwrap(),
+            json!("cached")
+        );
+        assert!(calls.lock().unwrap().is_empty());
+        assert_eq!(
+            p.handle(json!({"id": 1}), LambdaContext::fake()).unwrap(),
+            json!({"id": 1})
+        );
+        assert_eq!(*calls.lock().unwrap(), vec!["inner before", "inner after"]);
+    }
+
+    #[test]
+    fn reused() {
+        let mut seen = 0;
+        let mut p = pipeline(move |event: u32, _: LambdaContext| {
+            seen += event;
+            Ok(seen)
+        }).with_layer(Timing);
+        assert_eq!(Handler::call(&mut p, json!(2), LambdaContext::fake()).unwrap(), json!(2));
+        assert_eq!(Handler::call(&mut p, json!(3), LambdaContext::fake()).unwrap(), json!(5));
+    }
+}
@@ -3,21 +3,18 @@
 //!
 //! # Usage
 //!
-//! Add both crowbar and cpython to your `Cargo.toml`:
+//! Add crowbar to your `Cargo.toml`:
 //!
 //! ```toml
 //! [dependencies]
 //! crowbar = "0.3"
-//! cpython = "0.2"
 //! ```
 //!
-//! Use macros from both crates:
+//! Use its macros:
 //!
 //! ```rust
 //! #[macro_use]
 //! extern crate crowbar;
-//! #[macro_use]
-//! extern crate cpython;
 //! # fn main() { }
 //! ```
 //!
@@ -25,7 +22,6 @@
 //!
 //! ```rust
 //! # #[macro_use(lambda)] extern crate crowbar;
-//! # #[macro_use] extern crate cpython;
 //! # fn main() {
 //! lambda!(|event, context| {
 //!     println!("hi cloudwatch logs, this is {}", context.function_name());
@@ -35,11 +31,11 @@
 //! # }
 //! ```
 //!
-//! Alternatively, you can also just attribute a bare handler `fn` with `#[lambdafn]`
+//! Alternatively, you can also just attribute a bare handler `fn` with `#[lambdafn]` and export it
+//! with `module!`:
 //!
 //! ```rust
 //! # #[macro_use] extern crate crowbar;
-//! # #[macro_use] extern crate cpython;
 //! #[lambdafn]
 //! fn handler(
 //!     event: crowbar::Value,
@@ -47,6 +43,48 @@
 //! ) -> crowbar::LambdaResult {
 //!     Ok(event)
 //! }
+//!
+//! module!(handler);
+//! # fn main() { }
+//! ```
+//!
+//! Attributed functions can also take a typed event without a context, borrow the context, keep
+//! state between invocations of a warm container, or be `async`. A crate can export as many of
+//! them as it needs, and `#[lambdafn(name = "...")]` changes the name a function is exported as:
+//!
+//! ```rust
+//! # #[macro_use] extern crate crowbar;
+//! use std::cell::Cell;
+//! use crowbar::events::sqs::SqsEvent;
+//! use crowbar::{LambdaContext, LambdaResult, Value};
+//!
+//! #[lambdafn]
+//! fn count(event: SqsEvent, ctx: &LambdaContext, seen: &Cell<usize>) -> LambdaResult<usize> {
+//!     seen.set(seen.get() + event.records.len());
+//!     println!("{} has seen {} messages", ctx.function_name(), seen.get());
+//!     Ok(seen.get())
+//! }
+//!
+//! #[lambdafn(name = "echo")]
+//! fn echo_event(event: Value) -> LambdaResult {
+//!     Ok(event)
+//! }
+//!
+//! module!(count, echo_event);
+//! # fn main() { }
+//! ```
+//!
+//! ```rust,edition2018
+//! # #[macro_use] extern crate crowbar;
+//! # use crowbar::{LambdaResult, Value};
+//! # async fn lookup(_: &str) -> Option<String> { None }
+//! #[lambdafn]
+//! async fn handler(event: Value) -> LambdaResult<Option<String>> {
+//!     Ok(lookup(event["id"].as_str().unwrap_or_default()).await)
+//! }
+//!
+//! module!(handler);
+//! # fn main() { }
 //! ```
 //!
 //! # Building Lambda functions
@@ -55,9 +93,9 @@
 //! a dynamic library with the necessary functions for CPython to run. The `lambda!` macro does
 //! most of this for you, but cargo still needs to know what to do.
 //!
-//! You can configure cargo to build a dynamic library with the following. If you're using the
-//! `lambda!` macro as above, you need to use `lambda` for the library name (see the documentation
-//! for `lambda!` if you want to use something else).
+//! You can configure cargo to build a dynamic library with the following. The module `lambda!`
+//! and `module!` create is named after the library, so this builds a `liblambda` module (see the
+//! documentation for `lambda!` if you want to use something else).
 //!
 //! ```toml
 //! [lib]
@@ -95,17 +133,21 @@
 //!
 //! ```toml
 //! [dependencies]
-//! crowbar = { version = "0.3", default-features = false }
-//! cpython = { version = "0.2", default-features = false, features = ["python27-sys"] }
+//! crowbar = { version = "0.3", default-features = false, features = ["python27-sys"] }
 //! ```
 
 extern crate crowbar_attr;
 pub use crowbar_attr::*;
+extern crate base64;
 extern crate cpython;
 extern crate cpython_json;
+extern crate flate2;
 #[macro_use]
 extern crate log;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 
 #[cfg(feature = "error-chain")]
@@ -124,6 +166,10 @@ mod errors {
                 description("Rust Exception")
                 display("Rust Exception")
             }
+            TaskError(name: String, message: String) {
+                description("Step Functions task error")
+                display("{}: {}", name, message)
+            }
         }
     }
 }
@@ -132,8 +178,15 @@ pub use errors::ErrorKind::{PyException, RustError};
 #[cfg(feature = "error-chain")]
 pub use errors::Error;
 
-#[doc(hidden)]
-pub use cpython::{PyObject, PyResult};
+pub mod cfn;
+pub mod codepipeline;
+pub mod events;
+pub mod layer;
+pub mod py;
+pub mod rotation;
+pub mod sfn;
+
+pub use cpython::{PyErr, PyObject, PyResult, Python};
 pub use serde_json::value::Value;
 
 /// Result object that accepts `Ok(T)` or any `Err(Error)`.
@@ -141,13 +194,13 @@ pub use serde_json::value::Value;
 /// crowbar uses [error-chain](https://crates.io/crates/error-chain) under feature error-chain
 ///
 /// A PyException can be returned as an error, it is converted to a Python `Exception`, and the
-/// message will be used as the exception's arguments.
+/// message will be used as the exception's arguments. A `TaskError(name, message)` is converted to
+/// an exception named `name`, see `crowbar::sfn::TaskError`.
 /// If an Error type is returned, it is converted to a Python `RuntimeError`, and the `Display` string for
 /// the `Error` returned is used as the value.
 ///
 /// ```rust
 /// #[macro_use(lambda)] extern crate crowbar;
-/// #[macro_use] extern crate cpython;
 /// lambda!(|event, _context| {
 ///     match event["authorizationToken"].as_str() {
 ///         Some("unauthorized") => Err(crowbar::PyException("Unauthorized".to_string()).into()),
@@ -165,13 +218,38 @@ pub type LambdaResult<T = Value> = errors::Result<T>;
 /// that any `Error` can be thrown within your Lambda function.
 ///
 /// If an Error type is returned, it is converted to a Python `RuntimeError`, and the `Display` string for
-/// the `Error` returned is used as the value.
+/// the `Error` returned is used as the value. The exception is
+/// `crowbar::events::authorizer::Unauthorized`, which is converted to a Python `Exception` so that
+/// API Gateway authorizers can reject a request, and `crowbar::sfn::TaskError`, which is converted
+/// to an exception with the name of the error.
+///
+/// ```rust
+/// #[macro_use(lambda)] extern crate crowbar;
+/// use crowbar::events::authorizer::Unauthorized;
+/// lambda!(|event, _context| {
+///     match event["authorizationToken"].as_str() {
+///         Some("unauthorized") => Err(Unauthorized.into()),
+///         Some(_) => Ok(event),
+///         None => Err("missing token".into()),
+///     }
+/// });
+/// ```
 #[cfg(not(feature = "error-chain"))]
 pub type LambdaResult<T = Value> = Result<T, Box<std::error::Error>>;
 
-use cpython::{ObjectProtocol, PyErr, PyTuple, PyUnicode, Python, PythonObject,
-              PythonObjectWithTypeObject};
+/// The error of a `LambdaResult`.
+#[cfg(feature = "error-chain")]
+pub type LambdaError = errors::Error;
+/// The error of a `LambdaResult`.
+#[cfg(not(feature = "error-chain"))]
+pub type LambdaError = Box<dyn std::error::Error>;
+
+use cpython::{ObjectProtocol, PyTuple, PyUnicode, PythonObject, PythonObjectWithTypeObject};
 use cpython_json::{from_json, to_json};
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
 
 /// Provides a view into the `context` object available to Lambda functions.
 ///
@@ -179,6 +257,7 @@ use cpython_json::{from_json, to_json};
 /// (https://docs.aws.amazon.com/lambda/latest/dg/python-context-object.html) in the AWS Lambda
 /// docs.
 pub struct LambdaContext<'a> {
+    py: Option<Python<'a>>,
     string_storage: [String; 7],
     remaining_time: Box<Fn() -> Result<u64, ContextError> + 'a>
 }
@@ -187,7 +266,7 @@ impl<'a> LambdaContext<'a> {
     fn new(py: &'a Python, py_context: &'a PyObject) -> PyResult<Self> {
         macro_rules! str_attr {
             ($x:expr) => {
-                py_context.getattr(*py, $x)?.extract::<String>(*py)?;
+                py_context.getattr(*py, $x)?.extract::<String>(*py)?
             }
         }
 
@@ -202,6 +281,7 @@ impl<'a> LambdaContext<'a> {
         ];
 
         Ok(LambdaContext {
+            py: Some(*py),
             string_storage: string_storage,
             remaining_time: Box::new(move || {
                 py_context.call_method(
@@ -272,11 +352,18 @@ impl<'a> LambdaContext<'a> {
         (self.remaining_time)()
     }
 
+    /// The `Python` token for the GIL, which crowbar holds while the handler runs, for calling
+    /// Python libraries with the `py` module.
+    pub fn python(&self) -> Python<'a> {
+        self.py.expect("fake contexts don't hold the GIL")
+    }
+
     #[cfg(test)]
     pub fn fake() -> Self {
         let name = "Fake";
         let version = "LATEST";
         LambdaContext {
+            py: None,
             string_storage: [
                 name.into(),
                 version.into(),
@@ -373,18 +460,94 @@ impl std::error::Error for ContextError {
     }
 }
 
+/// A Lambda function handler, as an alternative to a closure.
+///
+/// Handlers are values, so they can carry their own configuration and be called directly in unit
+/// tests. Wrap one with `lambda!` by prefixing it with `impl`:
+///
+/// ```rust
+/// # #[macro_use(lambda)] extern crate crowbar;
+/// # fn main() {
+/// use crowbar::{Handler, LambdaContext, LambdaError, Value};
+///
+/// struct Greeter {
+///     greeting: &'static str,
+/// }
+///
+/// impl Handler for Greeter {
+///     type Event = Value;
+///     type Output = String;
+///     type Error = LambdaError;
+///
+///     fn call(&mut self, event: Value, _: LambdaContext) -> Result<String, LambdaError> {
+///         let name = event["name"].as_str().ok_or("missing name")?;
+///         Ok(format!("{}, {}!", self.greeting, name))
+///     }
+/// }
+///
+/// lambda!(impl Greeter { greeting: "Hello" });
+/// # }
+/// ```
+pub trait Handler {
+    /// The event, deserialized from the event Lambda sends.
+    type Event: serde::de::DeserializeOwned;
+    /// The value returned to Lambda.
+    type Output: serde::Serialize;
+    /// The error returned when the handler fails. It's converted into a `LambdaError`, which is
+    /// raised in Python as a `RuntimeError` with the error's `Display` string as the message.
+    type Error: Into<LambdaError>;
+
+    /// Handles one invocation, once the event has been deserialized. `lambda!` creates the handler
+    /// when Python imports the module and calls it for every invocation the container receives,
+    /// one at a time, so anything it keeps in `self` lasts while the container stays warm.
+    fn call(
+        &mut self,
+        event: Self::Event,
+        context: LambdaContext,
+    ) -> Result<Self::Output, Self::Error>;
+}
+
+impl<H> Handler for &mut H
+where
+    H: Handler + ?Sized,
+{
+    type Event = H::Event;
+    type Output = H::Output;
+    type Error = H::Error;
+
+    fn call(&mut self, event: H::Event, context: LambdaContext) -> Result<H::Output, H::Error> {
+        (**self).call(event, context)
+    }
+}
+
+#[doc(hidden)]
+pub fn call_handler<'a, H>(
+    handler: &mut H,
+    event: H::Event,
+    context: LambdaContext<'a>,
+) -> LambdaResult<H::Output>
+where
+    H: Handler,
+{
+    handler.call(event, context).map_err(Into::into)
+}
+
 #[doc(hidden)]
-pub fn handler<F, O>(
+pub fn handler<F, E, O>(
     py: Python,
     f: F,
     py_event: PyObject,
     py_context: PyObject,
 ) -> PyResult<PyObject>
 where
-    F: FnOnce(Value, LambdaContext) -> LambdaResult<O>,
+    F: FnOnce(E, LambdaContext) -> LambdaResult<O>,
+    E: serde::de::DeserializeOwned,
     O: serde::Serialize,
 {
     let event = to_json(py, &py_event).map_err(|e| e.to_pyerr(py))?;
+    let event = serde_json::value::from_value(event)
+        .map_err(cpython_json::JsonError::SerdeJsonError)
+        .map_err(|e| e.to_pyerr(py))?;
     f(event, LambdaContext::new(&py, &py_context)?)
         .map_err(|e| {
             error!("lambda error {:?}", e);
@@ -395,6 +558,21 @@ where
                     pvalue: Some(PyUnicode::new(py, &message).into_object()),
                     ptraceback: None,
                 },
+                #[cfg(feature = "error-chain")]
+                Error(errors::ErrorKind::TaskError(name, message), _) => {
+                    task_error(py, &name, &message)
+                }
+                #[cfg(not(feature = "error-chain"))]
+                ref e if e.is::<sfn::TaskError>() => {
+                    let e = e.downcast_ref::<sfn::TaskError>().unwrap();
+                    task_error(py, e.name(), e.message())
+                }
+                #[cfg(not(feature = "error-chain"))]
+                ref e if e.is::<events::authorizer::Unauthorized>() => PyErr {
+                    ptype: cpython::exc::Exception::type_object(py).into_object(),
+                    pvalue: Some(PyUnicode::new(py, &format!("{}", e)).into_object()),
+                    ptraceback: None,
+                },
                 _ => PyErr {
                     ptype: cpython::exc::RuntimeError::type_object(py).into_object(),
                     pvalue: Some(PyUnicode::new(py, &format!("{}", e)).into_object()),
@@ -410,6 +588,237 @@ where
         .and_then(|v| from_json(py, v).map_err(|e| e.to_pyerr(py)))
 }
 
+/// What crowbar's macros expand to, so that crates using them only need to depend on crowbar.
+#[doc(hidden)]
+pub mod __private {
+    use std::cell::RefCell;
+    use std::ffi::CString;
+    use std::marker::PhantomData;
+    use std::mem;
+    use std::os::raw::c_void;
+    use std::ptr;
+
+    use cpython::_detail::{ffi, handle_callback, PyObjectCallbackConverter};
+    use cpython::argparse::{get_kwargs, parse_args, ParamDescription};
+    use cpython::{exc, PyErr, PyObject, PyResult, PyTuple, Python};
+
+    use {Handler, LambdaContext, LambdaResult};
+
+    /// A handler as called from Python.
+    pub trait Invoke {
+        fn invoke(&mut self, py: Python, event: PyObject, context: PyObject) -> PyResult<PyObject>;
+    }
+
+    /// Converts the event and the output of a `Handler` to and from JSON.
+    pub struct Typed<H>(pub H);
+
+    impl<H: Handler> Invoke for Typed<H> {
+        fn invoke(&mut self, py: Python, event: PyObject, context: PyObject) -> PyResult<PyObject> {
+            let handler = &mut self.0;
+            ::handler(
+                py,
+                |event, context| ::call_handler(handler, event, context),
+                event,
+                context,
+            )
+        }
+    }
+
+    /// Passes the Python objects to a raw handler as they are.
+    pub struct RawFn<F>(F);
+
+    impl<F> RawFn<F>
+    where
+        F: FnMut(Python, &PyObject, &PyObject, LambdaContext) -> PyResult<PyObject>,
+    {
+        pub fn new(f: F) -> RawFn<F> {
+            RawFn(f)
+        }
+    }
+
+    impl<F> Invoke for RawFn<F>
+    where
+        F: FnMut(Python, &PyObject, &PyObject, LambdaContext) -> PyResult<PyObject>,
+    {
+        fn invoke(&mut self, py: Python, event: PyObject, context: PyObject) -> PyResult<PyObject> {
+            let lambda_context = LambdaContext::new(&py, &context)?;
+            (self.0)(py, &event, &context, lambda_context)
+        }
+    }
+
+    /// Whether a handler declared with `#[lambdafn(module = "...")]` for `declared`, if any, can be
+    /// exported from `module`.
+    pub const fn in_module(declared: Option<&str>, module: &str) -> bool {
+        let (declared, module) = match declared {
+            Some(declared) => (declared.as_bytes(), module.as_bytes()),
+            None => return true,
+        };
+        if declared.len() != module.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < declared.len() {
+            if declared[i] != module[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    pub use cpython::_detail::ffi::PyObject as RawPyObject;
+    pub use cpython::py_module_initializer;
+
+    /// Adapts a closure or function to `Handler`.
+    pub struct FnHandler<F, E, O> {
+        f: F,
+        marker: PhantomData<fn(E) -> O>,
+    }
+
+    impl<F, E, O> FnHandler<F, E, O>
+    where
+        F: FnMut(E, LambdaContext) -> LambdaResult<O>,
+    {
+        pub fn new(f: F) -> FnHandler<F, E, O> {
+            FnHandler {
+                f: f,
+                marker: PhantomData,
+            }
+        }
+    }
+
+    impl<F, E, O> Handler for FnHandler<F, E, O>
+    where
+        F: FnMut(E, LambdaContext) -> LambdaResult<O>,
+        E: ::serde::de::DeserializeOwned,
+        O: ::serde::Serialize,
+    {
+        type Event = E;
+        type Output = O;
+        type Error = ::LambdaError;
+
+        fn call(&mut self, event: E, context: LambdaContext) -> LambdaResult<O> {
+            (self.f)(event, context)
+        }
+    }
+
+    /// Creates a Python function named `name` that calls `handler`.
+    ///
+    /// The handler is kept in a capsule passed to the function as `self`, so it lives as long as
+    /// the function and is reused by every call. Python only calls it with the GIL held, but may
+    /// do so from any thread, hence `Send`; the `RefCell` turns a handler calling itself back
+    /// through Python into an exception instead of two `&mut` to it.
+    pub fn function<H>(py: Python, name: &str, handler: H) -> PyResult<PyObject>
+    where
+        H: Invoke + Send + 'static,
+    {
+        // Python refers to the definition for as long as the function exists, which is as long as
+        // the module is loaded.
+        let def = Box::new(ffi::PyMethodDef {
+            ml_name: CString::new(name)
+                .expect("handler names can't contain NUL")
+                .into_raw(),
+            ml_meth: Some(unsafe {
+                mem::transmute::<ffi::PyCFunctionWithKeywords, ffi::PyCFunction>(call::<H>)
+            }),
+            ml_flags: ffi::METH_VARARGS | ffi::METH_KEYWORDS,
+            ml_doc: ptr::null(),
+        });
+        unsafe {
+            let handler = Box::into_raw(Box::new(RefCell::new(handler)));
+            let capsule = ffi::PyCapsule_New(
+                handler as *mut c_void,
+                ptr::null(),
+                Some(drop_handler::<H>),
+            );
+            if capsule.is_null() {
+                drop(Box::from_raw(handler));
+                return Err(PyErr::fetch(py));
+            }
+            let capsule = PyObject::from_owned_ptr(py, capsule);
+            let function = ffi::PyCFunction_New(Box::into_raw(def), capsule.as_ptr());
+            if function.is_null() {
+                return Err(PyErr::fetch(py));
+            }
+            Ok(PyObject::from_owned_ptr(py, function))
+        }
+    }
+
+    unsafe extern "C" fn drop_handler<H>(capsule: *mut RawPyObject) {
+        let handler = ffi::PyCapsule_GetPointer(capsule, ptr::null()) as *mut RefCell<H>;
+        drop(Box::from_raw(handler));
+    }
+
+    /// Parses the `event` and `context` arguments of a call from Python and calls the handler in
+    /// the capsule `slf` with them.
+    unsafe extern "C" fn call<H>(
+        slf: *mut RawPyObject,
+        args: *mut RawPyObject,
+        kwargs: *mut RawPyObject,
+    ) -> *mut RawPyObject
+    where
+        H: Invoke,
+    {
+        handle_callback("crowbar handler", PyObjectCallbackConverter, |py| {
+            let args: PyTuple = PyObject::from_borrowed_ptr(py, args).unchecked_cast_into();
+            let kwargs = get_kwargs(py, kwargs);
+            let params = [
+                ParamDescription {
+                    name: "event",
+                    is_optional: false,
+                },
+                ParamDescription {
+                    name: "context",
+                    is_optional: false,
+                },
+            ];
+            let mut output = [None, None];
+            parse_args(py, None, &params, &args, kwargs.as_ref(), &mut output)?;
+            let (event, context) = match output {
+                [Some(event), Some(context)] => (event, context),
+                _ => unreachable!("parse_args checks for required arguments"),
+            };
+            let handler = &*(ffi::PyCapsule_GetPointer(slf, ptr::null()) as *const RefCell<H>);
+            let mut handler = handler.try_borrow_mut().map_err(|_| {
+                PyErr::new::<exc::RuntimeError, _>(py, "the handler is already running")
+            })?;
+            handler.invoke(py, event, context)
+        })
+    }
+}
+
+/// Creates an exception of a new class named `name`, so that Step Functions sees `name` as the
+/// error name.
+fn task_error(py: Python, name: &str, message: &str) -> PyErr {
+    PyErr {
+        ptype: PyErr::new_type(py, &format!("crowbar.{}", name), None, None).into_object(),
+        pvalue: Some(PyUnicode::new(py, message).into_object()),
+        ptraceback: None,
+    }
+}
+
+/// Runs an `async fn` handler to completion, parking the invoking thread while it waits.
+#[doc(hidden)]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
 #[macro_export]
 /// Macro to wrap a Lambda function handler.
 ///
@@ -421,14 +830,15 @@ where
 /// fn handler(event: Value, context: LambdaContext) -> LambdaResult
 /// ```
 ///
-/// To use this macro, you need to `macro_use` both crowbar *and* cpython, because crowbar
-/// references multiple cpython macros.
+/// The event can also be any type that implements serde's `Deserialize`, such as the types in
+/// `crowbar::events`, and the value returned can be anything that implements `Serialize`.
+///
+/// Crates using this macro don't need to depend on cpython; everything it expands to is provided by
+/// crowbar.
 ///
 /// ```rust,ignore
 /// #[macro_use(lambda)]
 /// extern crate crowbar;
-/// #[macro_use]
-/// extern crate cpython;
 /// ```
 ///
 /// # Examples
@@ -437,7 +847,6 @@ where
 ///
 /// ```rust
 /// # #[macro_use(lambda)] extern crate crowbar;
-/// # #[macro_use] extern crate cpython;
 /// # fn main() {
 /// lambda!(|event, context| {
 ///     println!("hello!");
@@ -450,7 +859,6 @@ where
 ///
 /// ```rust
 /// # #[macro_use(lambda)] extern crate crowbar;
-/// # #[macro_use] extern crate cpython;
 /// # fn main() {
 /// use crowbar::{Value, LambdaContext, LambdaResult};
 ///
@@ -463,13 +871,82 @@ where
 /// # }
 /// ```
 ///
+/// Closures whose arguments aren't annotated receive a `Value`. To receive a typed event,
+/// annotate the event argument:
+///
+/// ```rust
+/// # #[macro_use(lambda)] extern crate crowbar;
+/// # fn main() {
+/// use crowbar::events::cloudwatch_logs::CloudWatchLogsEvent;
+///
+/// lambda!(|event: CloudWatchLogsEvent, _context| {
+///     for entry in event.aws_logs.data.log_events {
+///         println!("{}", entry.message);
+///     }
+///     Ok(())
+/// });
+/// # }
+/// ```
+///
+/// Values of types implementing `Handler` can be wrapped too, by prefixing them with `impl`, such
+/// as `lambda!(impl Greeter::new())`. See `Handler` for an example.
+///
+/// # State
+///
+/// Each handler is created once, when Python imports the module, and is kept for as long as the
+/// module is loaded, which on Lambda is the life of a warm container. Closures are called as
+/// `FnMut` and `Handler`s through `&mut self`, so state they own, such as clients or caches,
+/// carries over from one invocation to the next:
+///
+/// ```rust
+/// # #[macro_use(lambda)] extern crate crowbar;
+/// # fn main() {
+/// use std::collections::HashMap;
+/// use crowbar::{LambdaContext, LambdaResult};
+///
+/// lambda!({
+///     let mut cache = HashMap::new();
+///     move |key: String, _context: LambdaContext| -> LambdaResult<usize> {
+///         let len = key.len();
+///         Ok(*cache.entry(key).or_insert(len))
+///     }
+/// });
+/// # }
+/// ```
+///
+/// Python only calls a handler while holding the GIL, so calls never overlap, but they can come
+/// from different threads, so handlers must be `Send` (and `'static`). A handler that calls itself
+/// back through Python gets a `RuntimeError` instead.
+///
+/// # Raw handlers
+///
+/// Handlers prefixed with `raw` are given the `Python` token and the event and context objects
+/// as Python passed them, along with the `LambdaContext`, and return a Python object. Nothing is
+/// converted to or from JSON, so they can take and return objects JSON can't represent, and the
+/// Python exceptions they return are raised as they are. Doing more with these objects than
+/// passing them around needs a dependency on the cpython version crowbar uses.
+///
+/// ```rust
+/// # #[macro_use(lambda)] extern crate crowbar;
+/// extern crate cpython;
+///
+/// use cpython::{ObjectProtocol, PyBytes, PythonObject};
+///
+/// # fn main() {
+/// lambda!(raw |py, event, _context, _lambda_context| {
+///     // Respond with the event's body as bytes instead of a string.
+///     let body = event.get_item(py, "body")?.extract::<String>(py)?;
+///     Ok(PyBytes::new(py, body.as_bytes()).into_object())
+/// });
+/// # }
+/// ```
+///
 /// # Multiple handlers
 ///
 /// You can define multiple handlers in the same module in a way similar to `match`:
 ///
 /// ```rust
 /// # #[macro_use(lambda)] extern crate crowbar;
-/// # #[macro_use] extern crate cpython;
 /// # fn main() {
 /// lambda! {
 ///     "one" => |event, context| { Ok("one") },
@@ -480,8 +957,8 @@ where
 ///
 /// # Changing the dynamic library name
 ///
-/// If you need to change the name of the built dynamic library, you first need to change the
-/// `[lib]` section in Cargo.toml:
+/// If you need to change the name of the built dynamic library, change the `[lib]` section in
+/// Cargo.toml:
 ///
 /// ```toml
 /// [lib]
@@ -489,65 +966,163 @@ where
 /// crate-type = ["cdylib"]
 /// ```
 ///
-/// You then also need to change the names of the library symbols, which you can do by extending
-/// upon the multiple handler version of `lambda!`:
+/// `lambda!` and `module!` name the module after the library Cargo builds, so this is all you
+/// need to do. You can also name the module explicitly, which fails to compile if it doesn't match
+/// the `[lib]` name:
 ///
-/// ```rust
-/// # #[macro_use(lambda)] extern crate crowbar;
-/// # #[macro_use] extern crate cpython;
-/// # fn main() {
+/// ```rust,ignore
+/// lambda! {
+///     crate kappa {
+///         "handler" => |event, context| { Ok("hi from libkappa") }
+///     }
+/// };
+///
+/// module! {
+///     crate kappa { handler }
+/// }
+/// ```
+///
+/// A `#[lambdafn]` function can name the module it belongs to with
+/// `#[lambdafn(module = "kappa")]`, which is checked against the `[lib]` name the same way, and
+/// against the module `module!` exports it from.
+///
+/// When the library isn't built by Cargo, the module is named `liblambda` unless it's named
+/// explicitly. The names of the module's symbols can also be given in full, which are checked to
+/// agree with each other and with the `[lib]` name:
+///
+/// ```rust,ignore
 /// lambda! {
 ///     crate (libkappa, initlibkappa, PyInit_libkappa) {
 ///         "handler" => |event, context| { Ok("hi from libkappa") }
 ///     }
 /// };
-/// # }
 /// ```
 macro_rules! lambda {
-    (@module ($module:ident, $py2:ident, $py3:ident)
-     @handlers ($($handler:expr => $target:expr),*)) => {
-        py_module_initializer!($module, $py2, $py3, |py, m| {
+    (@module $module:tt @handlers ($($handler:expr => $target:expr,)*)) => {
+        $crate::__py_module_initializer!($crate, $module, |py, m| {
             $(
-                m.add(py, $handler, py_fn!(
-                    py,
-                    x(
-                        event: $crate::PyObject,
-                        context: $crate::PyObject
-                    ) -> $crate::PyResult<$crate::PyObject> {
-                        $crate::handler(py, $target, event, context)
-                    }
-                ))?;
+                m.add(py, $handler, $crate::__private::function(py, $handler, $target)?)?;
             )*
             Ok(())
         });
     };
 
-    (crate $module:tt { $($handler:expr => $target:expr),* }) => {
-        lambda! { @module $module @handlers ($($handler => $target),*) }
+    // Closures without type annotations take a `Value`; without this the event type of
+    // `FnHandler` can't be inferred.
+    (@typed $module:tt ($($done:tt)*) $(,)*) => {
+        $crate::lambda! { @module $module @handlers ($($done)*) }
+    };
+
+    (@typed $module:tt ($($done:tt)*)
+     $handler:expr => |$event:tt, $context:tt| $body:expr, $($rest:tt)*) => {
+        $crate::lambda! {
+            @typed $module ($($done)*)
+                $handler => |$event: $crate::Value, $context: $crate::LambdaContext| $body,
+            $($rest)*
+        }
+    };
+
+    (@typed $module:tt ($($done:tt)*)
+     $handler:expr => move |$event:tt, $context:tt| $body:expr, $($rest:tt)*) => {
+        $crate::lambda! {
+            @typed $module ($($done)*)
+                $handler => move |$event: $crate::Value, $context: $crate::LambdaContext| $body,
+            $($rest)*
+        }
+    };
+
+    (@typed $module:tt ($($done:tt)*) $handler:expr => impl $target:expr, $($rest:tt)*) => {
+        $crate::lambda! {
+            @typed $module ($($done)* $handler => $crate::__private::Typed($target),) $($rest)*
+        }
     };
 
-    (crate $module:tt { $($handler:expr => $target:expr,)* }) => {
-        lambda! { @module $module @handlers ($($handler => $target),*) }
+    (@typed $module:tt ($($done:tt)*) $handler:expr => raw $target:expr, $($rest:tt)*) => {
+        $crate::lambda! {
+            @typed $module ($($done)* $handler => $crate::__private::RawFn::new($target),)
+            $($rest)*
+        }
     };
 
-    ($($handler:expr => $target:expr),*) => {
-        lambda! { @module (liblambda, initliblambda, PyInit_liblambda)
-                  @handlers ($($handler => $target),*) }
+    (@typed $module:tt ($($done:tt)*) $handler:expr => $target:expr, $($rest:tt)*) => {
+        $crate::lambda! {
+            @typed $module ($($done)*
+                $handler => $crate::__private::Typed($crate::__private::FnHandler::new($target)),)
+            $($rest)*
+        }
     };
 
-    ($($handler:expr => $target:expr,)*) => {
-        lambda! { $($handler => $target),* }
+    (crate $module:tt { $($handlers:tt)* }) => {
+        $crate::lambda! { @typed $module () $($handlers)*, }
     };
 
-    ($f:expr) => {
-        lambda! { "handler" => $f, }
+    ($handler:expr => $($handlers:tt)*) => {
+        $crate::lambda! { @typed _ () $handler => $($handlers)*, }
+    };
+
+    ($($f:tt)+) => {
+        $crate::lambda! { "handler" => $($f)+ }
     };
 }
 
+#[macro_export]
+/// Exports functions attributed with `#[lambdafn]` as handlers of a single module.
+///
+/// Each function is exported under its own name, or the name given with
+/// `#[lambdafn(name = "...")]`. Functions in other modules can be listed by path. Like `lambda!`,
+/// the module is named after the library unless it's named with `module! { crate kappa { ... } }`.
+/// A `#[lambdafn]` function that isn't listed is a compile error, rather than a handler Lambda
+/// can't find.
+///
+/// ```rust
+/// # #[macro_use] extern crate crowbar;
+/// mod users {
+///     use crowbar::{LambdaResult, Value};
+///
+///     #[lambdafn(name = "get_user")]
+///     pub fn get(event: Value) -> LambdaResult {
+///         Ok(event)
+///     }
+/// }
+///
+/// #[lambdafn]
+/// fn health(_event: crowbar::Value) -> crowbar::LambdaResult<&'static str> {
+///     Ok("ok")
+/// }
+///
+/// module!(users::get, health);
+/// # fn main() { }
+/// ```
+macro_rules! module {
+    (crate $module:tt { $($($handler:ident)::+),+ $(,)* }) => {
+        $(
+            impl $($handler)::+ {
+                #[doc(hidden)]
+                pub const __EXPORTED: () = ();
+            }
+        )+
+        $crate::__check_module!($crate, $module, $($($handler)::+),+);
+
+        $crate::lambda! {
+            crate $module {
+                $($($handler)::+::NAME => {
+                    let mut handler = $($handler)::+::handler();
+                    move |event, context: $crate::LambdaContext| {
+                        handler.call(event, context, |future| $crate::block_on(future))
+                    }
+                }),+
+            }
+        }
+    };
+
+    ($($($handler:ident)::+),+ $(,)*) => {
+        $crate::module! { crate _ { $($($handler)::+),+ } }
+    };
+}
 
 #[cfg(test)]
 mod tests {
-    use super::LambdaContext;
+    use super::{block_on, call_handler, Handler, LambdaContext};
     #[test]
     fn fake_context() {
         let fake = LambdaContext::fake()
@@ -568,4 +1143,71 @@ mod tests {
         assert_eq!(fake.log_stream_name(), "bazboom");
         assert_eq!(fake.get_remaining_time_in_millis(), Ok(5_000));
     }
-}
\ No newline at end of file
+
+    struct Counter {
+        count: u32,
+    }
+
+    impl Handler for Counter {
+        type Event = u32;
+        type Output = u32;
+        type Error = String;
+
+        fn call(&mut self, event: u32, context: LambdaContext) -> Result<u32, String> {
+            let step = context
+                .function_version()
+                .parse::<u32>()
+                .map_err(|e| e.to_string())?;
+            self.count += event * step;
+            Ok(self.count)
+        }
+    }
+
+    #[test]
+    fn handler_trait() {
+        let mut counter = Counter { count: 0 };
+        let context = || LambdaContext::fake().with_function_version("2");
+        assert_eq!(counter.call(1, context()), Ok(2));
+        assert_eq!(call_handler(&mut &mut counter, 3, context()).unwrap(), 8);
+        assert_eq!(counter.count, 8);
+
+        let latest = LambdaContext::fake().with_function_version("$LATEST");
+        let error = call_handler(&mut counter, 1, latest).unwrap_err();
+        assert_eq!(error.to_string(), "invalid digit found in string");
+    }
+
+    #[test]
+    fn block_on_pending() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        struct YieldOnce(bool);
+
+        impl Future for YieldOnce {
+            type Output = &'static str;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<&'static str> {
+                if self.0 {
+                    Poll::Ready("done")
+                } else {
+                    self.0 = true;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+
+        assert_eq!(block_on(YieldOnce(false)), "done");
+    }
+
+    #[test]
+    fn handler_module() {
+        use super::__private::in_module;
+
+        assert!(in_module(None, "libkappa"));
+        assert!(in_module(Some("libkappa"), "libkappa"));
+        assert!(!in_module(Some("libkappa"), "liblambda"));
+        assert!(!in_module(Some("libkappa"), "libkappa2"));
+    }
+}
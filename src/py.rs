@@ -0,0 +1,410 @@
+//! Calling Python libraries from handlers.
+//!
+//! The Lambda Python execution environment comes with [boto3], the AWS SDK for Python, so
+//! handlers can call AWS services through it instead of compiling an SDK (and linking OpenSSL)
+//! into the library. `Object` wraps a Python object: arguments are serialized with serde, results
+//! are deserialized, and Python exceptions are returned as `PyError`s.
+//!
+//! Calls need the `Python` token for the GIL, which crowbar holds while a handler runs. Raw
+//! handlers are given it, and other handlers get it from `LambdaContext::python`.
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! #[macro_use]
+//! extern crate serde_derive;
+//!
+//! use crowbar::py;
+//! use crowbar::{LambdaContext, LambdaResult, Value};
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename_all = "PascalCase")]
+//! struct ListBuckets {
+//!     buckets: Vec<Bucket>,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename_all = "PascalCase")]
+//! struct Bucket {
+//!     name: String,
+//!     creation_date: String,
+//! }
+//!
+//! fn buckets(_: Value, context: LambdaContext) -> LambdaResult<Vec<String>> {
+//!     let py = context.python();
+//!     let s3 = py::import(py, "boto3")?.call_method(py, "client", ("s3",), ())?;
+//!     let response: ListBuckets = s3.call_method(py, "list_buckets", (), ())?.extract(py)?;
+//!     Ok(response.buckets.into_iter().map(|bucket| bucket.name).collect())
+//! }
+//!
+//! # fn main() {
+//! lambda!(buckets);
+//! # }
+//! ```
+//!
+//! Since handlers are kept between invocations, a handler can create its clients once and reuse
+//! them.
+//!
+//! [boto3]: https://boto3.readthedocs.io/
+
+use std::error;
+use std::fmt;
+
+use base64;
+use cpython::{ObjectProtocol, PyBytes, PyDict, PyErr, PyList, PyObject, PyTuple, Python,
+              PythonObject};
+use cpython_json::{from_json, to_json, JsonError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Map, Number};
+
+use Value;
+
+/// Imports a Python module, such as `boto3`.
+pub fn import(py: Python, name: &str) -> Result<Object, PyError> {
+    py.import(name)
+        .map(|module| Object(module.into_object()))
+        .map_err(|e| PyError::new(py, e))
+}
+
+/// A Python object.
+#[derive(Debug)]
+pub struct Object(PyObject);
+
+impl Object {
+    /// Converts a value to a Python object through JSON.
+    pub fn new<T: Serialize>(py: Python, value: &T) -> Result<Object, PyError> {
+        to_object(py, value).map(Object)
+    }
+
+    /// Gets the attribute `name` of the object.
+    pub fn getattr(&self, py: Python, name: &str) -> Result<Object, PyError> {
+        self.0
+            .getattr(py, name)
+            .map(Object)
+            .map_err(|e| PyError::new(py, e))
+    }
+
+    /// Calls the object with positional arguments `args`, which must serialize to a sequence such
+    /// as a tuple, and keyword arguments `kwargs`, which must serialize to a map such as a struct.
+    /// Either can be `()` for none.
+    pub fn call<A, K>(&self, py: Python, args: A, kwargs: K) -> Result<Object, PyError>
+    where
+        A: Serialize,
+        K: Serialize,
+    {
+        let (args, kwargs) = arguments(py, &args, &kwargs)?;
+        self.0
+            .call(py, args, kwargs.as_ref())
+            .map(Object)
+            .map_err(|e| PyError::new(py, e))
+    }
+
+    /// Calls the method `name` of the object, with arguments like `call`.
+    pub fn call_method<A, K>(
+        &self,
+        py: Python,
+        name: &str,
+        args: A,
+        kwargs: K,
+    ) -> Result<Object, PyError>
+    where
+        A: Serialize,
+        K: Serialize,
+    {
+        let (args, kwargs) = arguments(py, &args, &kwargs)?;
+        self.0
+            .call_method(py, name, args, kwargs.as_ref())
+            .map(Object)
+            .map_err(|e| PyError::new(py, e))
+    }
+
+    /// Converts the object to JSON and deserializes it.
+    ///
+    /// Besides the types JSON has, dates and times become ISO 8601 strings, `Decimal`s become
+    /// numbers and `bytes` become base64 strings, as they are in the AWS APIs.
+    pub fn extract<T: DeserializeOwned>(&self, py: Python) -> Result<T, PyError> {
+        let value = to_value(py, &self.0).map_err(|e| PyError::new(py, e))?;
+        serde_json::from_value(value).map_err(|e| PyError::value_error(e.to_string()))
+    }
+
+    pub fn as_object(&self) -> &PyObject {
+        &self.0
+    }
+
+    pub fn into_object(self) -> PyObject {
+        self.0
+    }
+}
+
+impl From<PyObject> for Object {
+    fn from(object: PyObject) -> Object {
+        Object(object)
+    }
+}
+
+/// A Python exception, or a value that couldn't be converted to or from Python, reported as the
+/// `TypeError` or `ValueError` Python would raise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyError {
+    type_name: String,
+    message: String,
+    code: Option<String>,
+}
+
+impl PyError {
+    fn new(py: Python, mut e: PyErr) -> PyError {
+        let type_name = e.get_type(py).name(py).into_owned();
+        let instance = e.instance(py);
+        let message = match instance.str(py) {
+            Ok(message) => message.to_string_lossy(py).into_owned(),
+            Err(_) => String::new(),
+        };
+        // botocore's `ClientError` has the response from AWS, including the error code. Only the
+        // code is looked up, since the rest of the response can hold values JSON can't represent.
+        let code = instance
+            .getattr(py, "response")
+            .and_then(|response| response.get_item(py, "Error"))
+            .and_then(|error| error.get_item(py, "Code"))
+            .and_then(|code| code.extract::<String>(py))
+            .ok();
+        PyError {
+            type_name: type_name,
+            message: message,
+            code: code,
+        }
+    }
+
+    fn type_error(message: String) -> PyError {
+        PyError {
+            type_name: "TypeError".to_string(),
+            message: message,
+            code: None,
+        }
+    }
+
+    fn value_error(message: String) -> PyError {
+        PyError {
+            type_name: "ValueError".to_string(),
+            message: message,
+            code: None,
+        }
+    }
+
+    /// The name of the exception's class, such as `ClientError`.
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The error code of a botocore `ClientError`, such as `ResourceNotFoundException`.
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+}
+
+impl fmt::Display for PyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.type_name, self.message)
+    }
+}
+
+impl error::Error for PyError {
+    fn description(&self) -> &str {
+        "Python exception"
+    }
+}
+
+#[cfg(feature = "error-chain")]
+impl From<PyError> for ::Error {
+    fn from(e: PyError) -> ::Error {
+        ::errors::ErrorKind::PyException(e.to_string()).into()
+    }
+}
+
+fn to_object<T: Serialize>(py: Python, value: &T) -> Result<PyObject, PyError> {
+    let value = serde_json::to_value(value).map_err(|e| PyError::type_error(e.to_string()))?;
+    from_json(py, value).map_err(|e| PyError::new(py, e.to_pyerr(py)))
+}
+
+fn arguments<A, K>(py: Python, args: &A, kwargs: &K) -> Result<(PyTuple, Option<PyDict>), PyError>
+where
+    A: Serialize,
+    K: Serialize,
+{
+    let args = to_object(py, args)?;
+    let args = if args == py.None() {
+        PyTuple::empty(py)
+    } else if let Ok(list) = args.cast_as::<PyList>(py) {
+        PyTuple::new(py, &list.iter(py).collect::<Vec<_>>())
+    } else {
+        return Err(PyError::type_error(
+            "positional arguments must be a sequence".to_string(),
+        ));
+    };
+    let kwargs = to_object(py, kwargs)?;
+    let kwargs = if kwargs == py.None() {
+        None
+    } else {
+        match kwargs.cast_into::<PyDict>(py) {
+            Ok(kwargs) => Some(kwargs),
+            Err(_) => {
+                return Err(PyError::type_error(
+                    "keyword arguments must be a map".to_string(),
+                ))
+            }
+        }
+    };
+    Ok((args, kwargs))
+}
+
+/// Converts an object to JSON like `cpython_json::to_json`, and also the other types AWS
+/// responses contain.
+fn to_value(py: Python, object: &PyObject) -> Result<Value, PyErr> {
+    if let Ok(dict) = object.cast_as::<PyDict>(py) {
+        let mut map = Map::new();
+        for (key, value) in dict.items(py) {
+            let key = key.str(py)?.to_string_lossy(py).into_owned();
+            map.insert(key, to_value(py, &value)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if let Ok(list) = object.cast_as::<PyList>(py) {
+        return list.iter(py).map(|item| to_value(py, &item)).collect();
+    }
+    if let Ok(tuple) = object.cast_as::<PyTuple>(py) {
+        return tuple.iter(py).map(|item| to_value(py, item)).collect();
+    }
+    // `to_json` would truncate `Decimal`s to integers. The name of the C implementation's type
+    // includes the module.
+    if object.get_type(py).name(py).rsplit('.').next() == Some("Decimal") {
+        let number = object.str(py)?.to_string_lossy(py).into_owned();
+        return match number.parse::<Number>() {
+            Ok(number) => Ok(Value::Number(number)),
+            Err(_) => Err(JsonError::InvalidFloat.to_pyerr(py)),
+        };
+    }
+    let error = match to_json(py, object) {
+        Ok(value) => return Ok(value),
+        Err(e @ JsonError::TypeError(..)) => e.to_pyerr(py),
+        Err(e) => return Err(e.to_pyerr(py)),
+    };
+    if let Ok(bytes) = object.cast_as::<PyBytes>(py) {
+        return Ok(base64::encode(bytes.data(py)).into());
+    }
+    if object.hasattr(py, "isoformat")? {
+        let string = object.call_method(py, "isoformat", PyTuple::empty(py), None)?;
+        return Ok(string.extract::<String>(py)?.into());
+    }
+    Err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {LambdaContext, LambdaResult};
+
+    #[test]
+    fn call() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let json = import(py, "json").unwrap();
+        let args = (json!({"b": [1, 2], "a": null}),);
+        let dumped = json
+            .call_method(py, "dumps", args, json!({"sort_keys": true}))
+            .unwrap()
+            .extract::<String>(py)
+            .unwrap();
+        assert_eq!(dumped, r#"{"a": null, "b": [1, 2]}"#);
+
+        let loads = json.getattr(py, "loads").unwrap();
+        let loaded = loads.call(py, ("[1, 2.5, \"x\"]",), ()).unwrap();
+        assert_eq!(
+            loaded.extract::<(u8, f64, String)>(py).unwrap(),
+            (1, 2.5, "x".to_string())
+        );
+
+        let e = loads.call(py, ("[",), ()).unwrap_err();
+        assert_eq!(e.type_name(), "JSONDecodeError");
+        assert_eq!(e.code(), None);
+        assert!(loads.call(py, "[]", ()).is_err());
+        assert!(loads.call(py, (), vec![1]).is_err());
+        assert_eq!(
+            loaded.extract::<String>(py).unwrap_err().type_name(),
+            "ValueError"
+        );
+    }
+
+    #[test]
+    fn aws_types() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let date = import(py, "datetime")
+            .unwrap()
+            .call_method(py, "datetime", (2020, 1, 2, 3, 4, 5), ())
+            .unwrap();
+        let decimal = import(py, "decimal")
+            .unwrap()
+            .call_method(py, "Decimal", ("1.25",), ())
+            .unwrap();
+        let bytes = Object::new(py, &"crowbar")
+            .unwrap()
+            .call_method(py, "encode", (), ())
+            .unwrap();
+        let response = PyTuple::new(
+            py,
+            &[date.into_object(), decimal.into_object(), bytes.into_object()],
+        );
+        assert_eq!(
+            Object::from(response.into_object())
+                .extract::<Value>(py)
+                .unwrap(),
+            json!(["2020-01-02T03:04:05", 1.25, "Y3Jvd2Jhcg=="])
+        );
+    }
+
+    #[test]
+    fn client_error() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let raise = py
+            .eval(
+                "lambda: (_ for _ in ()).throw(type('ClientError', (Exception,), \
+                 {'response': {'Error': {'Code': 'ThrottlingException'}, \
+                 'ResponseMetadata': {'Date': __import__('datetime').date(2020, 1, 2)}}})\
+                 ('slow down'))",
+                None,
+                None,
+            )
+            .unwrap();
+        let e = Object::from(raise).call(py, (), ()).unwrap_err();
+        assert_eq!(e.code(), Some("ThrottlingException"));
+        assert_eq!(e.to_string(), "ClientError: slow down");
+    }
+
+    #[test]
+    fn context_python() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let context = py
+            .eval(
+                "type('Context', (), dict(function_name='py', function_version='1', \
+                 invoked_function_arn='arn', memory_limit_in_mb='128', aws_request_id='id', \
+                 log_group_name='group', log_stream_name='stream', \
+                 get_remaining_time_in_millis=lambda self: 1000))()",
+                None,
+                None,
+            )
+            .unwrap();
+        let dumps = |event: Value, context: LambdaContext| -> LambdaResult<String> {
+            let py = context.python();
+            let dumped = import(py, "json")?.call_method(py, "dumps", (event,), ())?;
+            Ok(dumped.extract(py)?)
+        };
+        let event = from_json(py, json!([1, "two"])).unwrap();
+        let output = ::handler(py, dumps, event, context).unwrap();
+        assert_eq!(output.extract::<String>(py).unwrap(), r#"[1, "two"]"#);
+    }
+}
@@ -0,0 +1,839 @@
+//! Secrets Manager rotation functions.
+//!
+//! Secrets Manager [rotates a secret]
+//! (https://docs.aws.amazon.com/secretsmanager/latest/userguide/rotating-secrets-lambda-function-overview.html)
+//! by invoking its rotation function four times, once for each step:
+//!
+//! 1. `createSecret` creates a new version of the secret, labelled `AWSPENDING`.
+//! 2. `setSecret` changes the credentials in the database or service to the pending version.
+//! 3. `testSecret` checks that the pending version works.
+//! 4. `finishSecret` moves the `AWSCURRENT` label to the pending version.
+//!
+//! Implement `RotationStrategy` for your secret and wrap it with `rotation`. crowbar checks that
+//! the version being rotated is pending before calling the method for the step, and provides the
+//! usual `finishSecret` implementation.
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::LambdaResult;
+//! use crowbar::rotation::{rotation, RotationEvent, RotationStrategy, SecretsManager, Version};
+//!
+//! struct Password;
+//!
+//! impl RotationStrategy for Password {
+//!     fn create_secret(
+//!         &mut self,
+//!         client: &mut dyn SecretsManager,
+//!         event: &RotationEvent,
+//!     ) -> LambdaResult<()> {
+//!         let pending = Version::Id(&event.client_request_token);
+//!         if client.get_secret_value(&event.secret_id, pending)?.is_none() {
+//!             let password = client.get_random_password(32, "/@\"'\\")?;
+//!             client.put_secret_value(
+//!                 &event.secret_id,
+//!                 &event.client_request_token,
+//!                 &password,
+//!                 "AWSPENDING",
+//!             )?;
+//!         }
+//!         Ok(())
+//!     }
+//!
+//!     fn set_secret(&mut self, _: &mut dyn SecretsManager, _: &RotationEvent) -> LambdaResult<()> {
+//!         // change the password in the database
+//!         Ok(())
+//!     }
+//!
+//!     fn test_secret(&mut self, _: &mut dyn SecretsManager, _: &RotationEvent) -> LambdaResult<()> {
+//!         // log in to the database with the new password
+//!         Ok(())
+//!     }
+//! }
+//!
+//! lambda!(|event, context| rotation(Password).handle(event, context));
+//! # }
+//! ```
+//!
+//! The Secrets Manager client is a `SecretsManager` trait object, so strategies can be tested with
+//! an `InMemorySecretsManager`.
+
+use std::collections::HashMap;
+
+use cpython::{NoArgs, ObjectProtocol, PyDict, PyErr, PyObject, PyResult, Python};
+use cpython_json::{from_json, to_json};
+use serde_json::{self, Value};
+
+use {LambdaContext, LambdaResult};
+
+/// The event a rotation function receives for each step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RotationEvent {
+    pub step: Step,
+    /// The ARN of the secret.
+    pub secret_id: String,
+    /// The ID of the version being rotated to.
+    pub client_request_token: String,
+    #[serde(default)]
+    pub rotation_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Step {
+    CreateSecret,
+    SetSecret,
+    TestSecret,
+    FinishSecret,
+}
+
+/// Rotates a secret, with one method per step of the rotation.
+///
+/// Secrets Manager retries steps that fail, so every method should be safe to call more than once.
+pub trait RotationStrategy {
+    /// Creates the pending version of the secret, with `event.client_request_token` as its version
+    /// ID and the `AWSPENDING` label.
+    fn create_secret(
+        &mut self,
+        client: &mut dyn SecretsManager,
+        event: &RotationEvent,
+    ) -> LambdaResult<()>;
+
+    /// Changes the credentials in the database or service to the pending version.
+    fn set_secret(&mut self, client: &mut dyn SecretsManager, event: &RotationEvent)
+        -> LambdaResult<()>;
+
+    /// Checks that the pending version works.
+    fn test_secret(
+        &mut self,
+        client: &mut dyn SecretsManager,
+        event: &RotationEvent,
+    ) -> LambdaResult<()>;
+
+    /// Makes the pending version current. Defaults to `finish_secret`.
+    fn finish_secret(
+        &mut self,
+        client: &mut dyn SecretsManager,
+        event: &RotationEvent,
+    ) -> LambdaResult<()> {
+        finish_secret(client, event)
+    }
+}
+
+impl<S> RotationStrategy for &mut S
+where
+    S: RotationStrategy + ?Sized,
+{
+    fn create_secret(
+        &mut self,
+        client: &mut dyn SecretsManager,
+        event: &RotationEvent,
+    ) -> LambdaResult<()> {
+        (**self).create_secret(client, event)
+    }
+
+    fn set_secret(&mut self, client: &mut dyn SecretsManager, event: &RotationEvent)
+        -> LambdaResult<()> {
+        (**self).set_secret(client, event)
+    }
+
+    fn test_secret(
+        &mut self,
+        client: &mut dyn SecretsManager,
+        event: &RotationEvent,
+    ) -> LambdaResult<()> {
+        (**self).test_secret(client, event)
+    }
+
+    fn finish_secret(
+        &mut self,
+        client: &mut dyn SecretsManager,
+        event: &RotationEvent,
+    ) -> LambdaResult<()> {
+        (**self).finish_secret(client, event)
+    }
+}
+
+/// Moves the `AWSCURRENT` label to the version being rotated to. Secrets Manager labels the
+/// version it was moved from `AWSPREVIOUS`.
+pub fn finish_secret(client: &mut dyn SecretsManager, event: &RotationEvent) -> LambdaResult<()> {
+    let description = client.describe_secret(&event.secret_id)?;
+    let current = description.version_with_stage("AWSCURRENT").map(String::from);
+    if current.as_ref() == Some(&event.client_request_token) {
+        return Ok(());
+    }
+    client.update_secret_version_stage(
+        &event.secret_id,
+        "AWSCURRENT",
+        Some(&event.client_request_token),
+        current.as_deref(),
+    )
+}
+
+/// Selects a version of a secret by ID or by label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version<'a> {
+    Id(&'a str),
+    Stage(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SecretDescription {
+    pub rotation_enabled: bool,
+    /// The labels of each version of the secret, by version ID.
+    pub version_ids_to_stages: HashMap<String, Vec<String>>,
+}
+
+impl SecretDescription {
+    /// The ID of the version with the label `stage`.
+    pub fn version_with_stage(&self, stage: &str) -> Option<&str> {
+        self.version_ids_to_stages
+            .iter()
+            .find(|&(_, stages)| stages.iter().any(|s| s == stage))
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretValue {
+    pub version_id: String,
+    pub version_stages: Vec<String>,
+    /// `None` for binary secrets.
+    pub secret_string: Option<String>,
+}
+
+/// The Secrets Manager API calls used to rotate secrets.
+pub trait SecretsManager {
+    fn describe_secret(&mut self, secret_id: &str) -> LambdaResult<SecretDescription>;
+
+    /// Gets a version of the secret, or `None` if it doesn't exist.
+    fn get_secret_value(
+        &mut self,
+        secret_id: &str,
+        version: Version,
+    ) -> LambdaResult<Option<SecretValue>>;
+
+    /// Creates a version of the secret with the ID `client_request_token`, moving `version_stage`
+    /// to it.
+    fn put_secret_value(
+        &mut self,
+        secret_id: &str,
+        client_request_token: &str,
+        secret_string: &str,
+        version_stage: &str,
+    ) -> LambdaResult<()>;
+
+    /// Moves `version_stage` from `remove_from_version_id` to `move_to_version_id`.
+    fn update_secret_version_stage(
+        &mut self,
+        secret_id: &str,
+        version_stage: &str,
+        move_to_version_id: Option<&str>,
+        remove_from_version_id: Option<&str>,
+    ) -> LambdaResult<()>;
+
+    fn get_random_password(&mut self, length: u32, exclude_characters: &str)
+        -> LambdaResult<String>;
+}
+
+/// Calls Secrets Manager with boto3, which is available in the Lambda execution environment.
+///
+/// The client uses the endpoint in the `SECRETS_MANAGER_ENDPOINT` environment variable if it is
+/// set, like the rotation functions AWS provides.
+#[derive(Default)]
+pub struct Boto3SecretsManager {
+    client: Option<PyObject>,
+}
+
+impl Boto3SecretsManager {
+    pub fn new() -> Boto3SecretsManager {
+        Boto3SecretsManager::default()
+    }
+
+    fn call(&mut self, py: Python, method: &str, kwargs: &[(&str, Value)]) -> PyResult<PyObject> {
+        if self.client.is_none() {
+            let kwargs = PyDict::new(py);
+            if let Some(endpoint) = ::std::env::var_os("SECRETS_MANAGER_ENDPOINT") {
+                kwargs.set_item(py, "endpoint_url", endpoint.to_string_lossy().as_ref())?;
+            }
+            let client = py
+                .import("boto3")?
+                .call(py, "client", ("secretsmanager",), Some(&kwargs))?;
+            self.client = Some(client);
+        }
+        let dict = PyDict::new(py);
+        for &(key, ref value) in kwargs {
+            let value = from_json(py, value.clone()).map_err(|e| e.to_pyerr(py))?;
+            dict.set_item(py, key, value)?;
+        }
+        self.client
+            .as_ref()
+            .unwrap()
+            .call_method(py, method, NoArgs, Some(&dict))
+    }
+
+    fn field(py: Python, response: &PyObject, key: &str) -> PyResult<Value> {
+        let value = response.call_method(py, "get", (key,), None)?;
+        to_json(py, &value).map_err(|e| e.to_pyerr(py))
+    }
+}
+
+/// The `Error.Code` of a botocore `ClientError`.
+fn error_code(py: Python, e: &mut PyErr) -> Option<String> {
+    let response = e.instance(py).getattr(py, "response").ok()?;
+    let response = to_json(py, &response).ok()?;
+    response["Error"]["Code"].as_str().map(String::from)
+}
+
+fn api_error(py: Python, operation: &str, mut e: PyErr) -> String {
+    format!("{} failed: {}", operation, e.instance(py))
+}
+
+impl SecretsManager for Boto3SecretsManager {
+    fn describe_secret(&mut self, secret_id: &str) -> LambdaResult<SecretDescription> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let (enabled, stages) = self
+            .call(py, "describe_secret", &[("SecretId", secret_id.into())])
+            .and_then(|response| {
+                Ok((
+                    Self::field(py, &response, "RotationEnabled")?,
+                    Self::field(py, &response, "VersionIdsToStages")?,
+                ))
+            })
+            .map_err(|e| api_error(py, "DescribeSecret", e))?;
+        Ok(SecretDescription {
+            rotation_enabled: enabled.as_bool().unwrap_or(false),
+            version_ids_to_stages: if stages.is_null() {
+                HashMap::new()
+            } else {
+                serde_json::from_value(stages).map_err(|e| e.to_string())?
+            },
+        })
+    }
+
+    fn get_secret_value(
+        &mut self,
+        secret_id: &str,
+        version: Version,
+    ) -> LambdaResult<Option<SecretValue>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let version = match version {
+            Version::Id(id) => ("VersionId", id.into()),
+            Version::Stage(stage) => ("VersionStage", stage.into()),
+        };
+        let response = match self.call(
+            py,
+            "get_secret_value",
+            &[("SecretId", secret_id.into()), version],
+        ) {
+            Ok(response) => response,
+            Err(mut e) => {
+                let code = error_code(py, &mut e);
+                if code.as_deref() == Some("ResourceNotFoundException") {
+                    return Ok(None);
+                }
+                return Err(api_error(py, "GetSecretValue", e).into());
+            }
+        };
+        let (id, stages, string) = (|| -> PyResult<_> {
+            Ok((
+                Self::field(py, &response, "VersionId")?,
+                Self::field(py, &response, "VersionStages")?,
+                Self::field(py, &response, "SecretString")?,
+            ))
+        })().map_err(|e| api_error(py, "GetSecretValue", e))?;
+        Ok(Some(SecretValue {
+            version_id: serde_json::from_value(id).map_err(|e| e.to_string())?,
+            version_stages: serde_json::from_value(stages).map_err(|e| e.to_string())?,
+            secret_string: serde_json::from_value(string).map_err(|e| e.to_string())?,
+        }))
+    }
+
+    fn put_secret_value(
+        &mut self,
+        secret_id: &str,
+        client_request_token: &str,
+        secret_string: &str,
+        version_stage: &str,
+    ) -> LambdaResult<()> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        self.call(
+            py,
+            "put_secret_value",
+            &[
+                ("SecretId", secret_id.into()),
+                ("ClientRequestToken", client_request_token.into()),
+                ("SecretString", secret_string.into()),
+                ("VersionStages", vec![version_stage].into()),
+            ],
+        ).map_err(|e| api_error(py, "PutSecretValue", e))?;
+        Ok(())
+    }
+
+    fn update_secret_version_stage(
+        &mut self,
+        secret_id: &str,
+        version_stage: &str,
+        move_to_version_id: Option<&str>,
+        remove_from_version_id: Option<&str>,
+    ) -> LambdaResult<()> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let mut kwargs = vec![
+            ("SecretId", secret_id.into()),
+            ("VersionStage", version_stage.into()),
+        ];
+        if let Some(id) = move_to_version_id {
+            kwargs.push(("MoveToVersionId", id.into()));
+        }
+        if let Some(id) = remove_from_version_id {
+            kwargs.push(("RemoveFromVersionId", id.into()));
+        }
+        self.call(py, "update_secret_version_stage", &kwargs)
+            .map_err(|e| api_error(py, "UpdateSecretVersionStage", e))?;
+        Ok(())
+    }
+
+    fn get_random_password(
+        &mut self,
+        length: u32,
+        exclude_characters: &str,
+    ) -> LambdaResult<String> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let password = self
+            .call(
+                py,
+                "get_random_password",
+                &[
+                    ("PasswordLength", length.into()),
+                    ("ExcludeCharacters", exclude_characters.into()),
+                ],
+            )
+            .and_then(|response| Self::field(py, &response, "RandomPassword"))
+            .map_err(|e| api_error(py, "GetRandomPassword", e))?;
+        Ok(serde_json::from_value(password).map_err(|e| e.to_string())?)
+    }
+}
+
+/// A `SecretsManager` that keeps secrets in memory, for testing rotation strategies.
+///
+/// Labels move between versions like they do in Secrets Manager, including `AWSPREVIOUS` following
+/// `AWSCURRENT`. Random passwords are a predictable sequence of letters.
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySecretsManager {
+    secrets: HashMap<String, InMemorySecret>,
+    passwords: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct InMemorySecret {
+    rotation_enabled: bool,
+    versions: HashMap<String, (Option<String>, Vec<String>)>,
+}
+
+impl InMemorySecretsManager {
+    pub fn new() -> InMemorySecretsManager {
+        InMemorySecretsManager::default()
+    }
+
+    /// Adds a secret with rotation enabled and one `AWSCURRENT` version.
+    pub fn with_secret(mut self, secret_id: &str, version_id: &str, secret_string: &str) -> Self {
+        let mut secret = InMemorySecret {
+            rotation_enabled: true,
+            versions: HashMap::new(),
+        };
+        secret.versions.insert(
+            version_id.to_string(),
+            (Some(secret_string.to_string()), vec!["AWSCURRENT".to_string()]),
+        );
+        self.secrets.insert(secret_id.to_string(), secret);
+        self
+    }
+
+    /// Adds an `AWSPENDING` version without a value, like Secrets Manager does when it starts
+    /// rotating a secret.
+    pub fn with_pending_version(mut self, secret_id: &str, version_id: &str) -> Self {
+        if let Some(secret) = self.secrets.get_mut(secret_id) {
+            secret.versions.insert(version_id.to_string(), (None, vec![]));
+            secret.move_stage("AWSPENDING", Some(version_id));
+        }
+        self
+    }
+
+    /// Turns rotation off for a secret added with `with_secret`.
+    pub fn with_rotation_disabled(mut self, secret_id: &str) -> Self {
+        if let Some(secret) = self.secrets.get_mut(secret_id) {
+            secret.rotation_enabled = false;
+        }
+        self
+    }
+
+    fn secret(&mut self, secret_id: &str) -> LambdaResult<&mut InMemorySecret> {
+        match self.secrets.get_mut(secret_id) {
+            Some(secret) => Ok(secret),
+            None => Err(format!("secret {} not found", secret_id).into()),
+        }
+    }
+}
+
+impl InMemorySecret {
+    fn move_stage(&mut self, stage: &str, to: Option<&str>) {
+        for &mut (_, ref mut stages) in self.versions.values_mut() {
+            stages.retain(|s| s != stage);
+        }
+        if let Some(&mut (_, ref mut stages)) = to.and_then(|id| self.versions.get_mut(id)) {
+            stages.push(stage.to_string());
+        }
+    }
+}
+
+impl SecretsManager for InMemorySecretsManager {
+    fn describe_secret(&mut self, secret_id: &str) -> LambdaResult<SecretDescription> {
+        let secret = self.secret(secret_id)?;
+        Ok(SecretDescription {
+            rotation_enabled: secret.rotation_enabled,
+            version_ids_to_stages: secret
+                .versions
+                .iter()
+                .map(|(id, (_, stages))| (id.clone(), stages.clone()))
+                .collect(),
+        })
+    }
+
+    fn get_secret_value(
+        &mut self,
+        secret_id: &str,
+        version: Version,
+    ) -> LambdaResult<Option<SecretValue>> {
+        let secret = self.secret(secret_id)?;
+        Ok(secret
+            .versions
+            .iter()
+            .find(|(id, (_, stages))| match version {
+                Version::Id(version_id) => *id == version_id,
+                Version::Stage(stage) => stages.iter().any(|s| s == stage),
+            })
+            .and_then(|(id, (string, stages))| {
+                string.as_ref().map(|string| SecretValue {
+                    version_id: id.clone(),
+                    version_stages: stages.clone(),
+                    secret_string: Some(string.clone()),
+                })
+            }))
+    }
+
+    fn put_secret_value(
+        &mut self,
+        secret_id: &str,
+        client_request_token: &str,
+        secret_string: &str,
+        version_stage: &str,
+    ) -> LambdaResult<()> {
+        let secret = self.secret(secret_id)?;
+        {
+            let version = secret
+                .versions
+                .entry(client_request_token.to_string())
+                .or_insert((None, vec![]));
+            match version.0 {
+                Some(ref existing) if existing != secret_string => {
+                    return Err(format!("version {} already exists", client_request_token).into())
+                }
+                _ => version.0 = Some(secret_string.to_string()),
+            }
+        }
+        secret.move_stage(version_stage, Some(client_request_token));
+        Ok(())
+    }
+
+    fn update_secret_version_stage(
+        &mut self,
+        secret_id: &str,
+        version_stage: &str,
+        move_to_version_id: Option<&str>,
+        remove_from_version_id: Option<&str>,
+    ) -> LambdaResult<()> {
+        let secret = self.secret(secret_id)?;
+        let attached = secret
+            .versions
+            .iter()
+            .find(|(_, (_, stages))| stages.iter().any(|s| s == version_stage))
+            .map(|(id, _)| id.clone());
+        if let Some(ref attached) = attached {
+            if Some(attached.as_str()) != remove_from_version_id {
+                return Err(format!(
+                    "{} is attached to version {}, which isn't being removed",
+                    version_stage, attached
+                ).into());
+            }
+        }
+        if let Some(id) = move_to_version_id {
+            if !secret.versions.contains_key(id) {
+                return Err(format!("version {} not found", id).into());
+            }
+        }
+        secret.move_stage(version_stage, move_to_version_id);
+        if version_stage == "AWSCURRENT" && move_to_version_id.is_some() {
+            secret.move_stage("AWSPREVIOUS", attached.as_deref());
+        }
+        Ok(())
+    }
+
+    fn get_random_password(
+        &mut self,
+        length: u32,
+        exclude_characters: &str,
+    ) -> LambdaResult<String> {
+        let alphabet: Vec<char> = (b'a'..b'z' + 1)
+            .map(char::from)
+            .filter(|c| !exclude_characters.contains(*c))
+            .collect();
+        if alphabet.is_empty() {
+            return Err("every character of the password alphabet is excluded".into());
+        }
+        self.passwords += 1;
+        Ok((0..length)
+            .map(|i| alphabet[((self.passwords + i) as usize) % alphabet.len()])
+            .collect())
+    }
+}
+
+impl<T> SecretsManager for &mut T
+where
+    T: SecretsManager + ?Sized,
+{
+    fn describe_secret(&mut self, secret_id: &str) -> LambdaResult<SecretDescription> {
+        (**self).describe_secret(secret_id)
+    }
+
+    fn get_secret_value(
+        &mut self,
+        secret_id: &str,
+        version: Version,
+    ) -> LambdaResult<Option<SecretValue>> {
+        (**self).get_secret_value(secret_id, version)
+    }
+
+    fn put_secret_value(
+        &mut self,
+        secret_id: &str,
+        client_request_token: &str,
+        secret_string: &str,
+        version_stage: &str,
+    ) -> LambdaResult<()> {
+        (**self).put_secret_value(secret_id, client_request_token, secret_string, version_stage)
+    }
+
+    fn update_secret_version_stage(
+        &mut self,
+        secret_id: &str,
+        version_stage: &str,
+        move_to_version_id: Option<&str>,
+        remove_from_version_id: Option<&str>,
+    ) -> LambdaResult<()> {
+        (**self).update_secret_version_stage(
+            secret_id,
+            version_stage,
+            move_to_version_id,
+            remove_from_version_id,
+        )
+    }
+
+    fn get_random_password(
+        &mut self,
+        length: u32,
+        exclude_characters: &str,
+    ) -> LambdaResult<String> {
+        (**self).get_random_password(length, exclude_characters)
+    }
+}
+
+/// Wraps a `RotationStrategy` into a handler that runs the step in each event.
+pub fn rotation<S>(strategy: S) -> RotationHandler<S>
+where
+    S: RotationStrategy,
+{
+    RotationHandler {
+        strategy: strategy,
+        client: Boto3SecretsManager::new(),
+    }
+}
+
+/// Handler for a rotation function, created by `rotation`.
+pub struct RotationHandler<S, C = Boto3SecretsManager> {
+    strategy: S,
+    client: C,
+}
+
+impl<S, C> RotationHandler<S, C>
+where
+    S: RotationStrategy,
+    C: SecretsManager,
+{
+    /// Replaces the `Boto3SecretsManager` used to call Secrets Manager.
+    pub fn with_client<T>(self, client: T) -> RotationHandler<S, T>
+    where
+        T: SecretsManager,
+    {
+        RotationHandler {
+            strategy: self.strategy,
+            client: client,
+        }
+    }
+
+    /// Runs the step for `event`.
+    ///
+    /// This fails without calling the strategy if rotation is disabled for the secret or the
+    /// version being rotated to isn't `AWSPENDING`, and does nothing if the version is already
+    /// `AWSCURRENT`.
+    pub fn handle(mut self, event: Value, _context: LambdaContext) -> LambdaResult<()> {
+        let event: RotationEvent = serde_json::from_value(event)
+            .map_err(|e| format!("not a Secrets Manager rotation event: {}", e))?;
+        let description = self.client.describe_secret(&event.secret_id)?;
+        if !description.rotation_enabled {
+            return Err(format!("secret {} is not enabled for rotation", event.secret_id).into());
+        }
+        let stages = description
+            .version_ids_to_stages
+            .get(&event.client_request_token)
+            .ok_or_else(|| {
+                format!(
+                    "secret version {} has no stage for rotation of secret {}",
+                    event.client_request_token, event.secret_id
+                )
+            })?;
+        if stages.iter().any(|s| s == "AWSCURRENT") {
+            info!(
+                "secret version {} already set as AWSCURRENT for secret {}",
+                event.client_request_token, event.secret_id
+            );
+            return Ok(());
+        }
+        if !stages.iter().any(|s| s == "AWSPENDING") {
+            return Err(format!(
+                "secret version {} not set as AWSPENDING for rotation of secret {}",
+                event.client_request_token, event.secret_id
+            ).into());
+        }
+
+        let client = &mut self.client;
+        match event.step {
+            Step::CreateSecret => self.strategy.create_secret(client, &event),
+            Step::SetSecret => self.strategy.set_secret(client, &event),
+            Step::TestSecret => self.strategy.test_secret(client, &event),
+            Step::FinishSecret => self.strategy.finish_secret(client, &event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LambdaContext;
+
+    const SECRET: &str = "arn:aws:secretsmanager:us-east-1:123456789012:secret:db";
+
+    #[derive(Default)]
+    struct Password {
+        set: Vec<String>,
+    }
+
+    impl RotationStrategy for Password {
+        fn create_secret(
+            &mut self,
+            client: &mut dyn SecretsManager,
+            event: &RotationEvent,
+        ) -> LambdaResult<()> {
+            let pending = Version::Id(&event.client_request_token);
+            if client.get_secret_value(&event.secret_id, pending)?.is_none() {
+                let password = client.get_random_password(8, "")?;
+                client.put_secret_value(
+                    &event.secret_id,
+                    &event.client_request_token,
+                    &password,
+                    "AWSPENDING",
+                )?;
+            }
+            Ok(())
+        }
+
+        fn set_secret(
+            &mut self,
+            client: &mut dyn SecretsManager,
+            event: &RotationEvent,
+        ) -> LambdaResult<()> {
+            let pending = client
+                .get_secret_value(&event.secret_id, Version::Stage("AWSPENDING"))?
+                .ok_or("no pending version")?;
+            self.set.push(pending.secret_string.unwrap());
+            Ok(())
+        }
+
+        fn test_secret(&mut self, _: &mut dyn SecretsManager, _: &RotationEvent) -> LambdaResult<()> {
+            Ok(())
+        }
+    }
+
+    fn event(step: &str, token: &str) -> Value {
+        json!({"Step": step, "SecretId": SECRET, "ClientRequestToken": token})
+    }
+
+    #[test]
+    fn rotates() {
+        let mut client = InMemorySecretsManager::new()
+            .with_secret(SECRET, "v1", "hunter2")
+            .with_pending_version(SECRET, "v2");
+        let mut strategy = Password::default();
+        for step in &["createSecret", "setSecret", "testSecret", "finishSecret", "finishSecret"] {
+            rotation(&mut strategy)
+                .with_client(&mut client)
+                .handle(event(step, "v2"), LambdaContext::fake())
+                .unwrap();
+        }
+
+        let current = client
+            .get_secret_value(SECRET, Version::Stage("AWSCURRENT"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(current.version_id, "v2");
+        assert_eq!(strategy.set, vec![current.secret_string.unwrap()]);
+        let previous = client
+            .get_secret_value(SECRET, Version::Stage("AWSPREVIOUS"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(previous.secret_string, Some("hunter2".to_string()));
+    }
+
+    #[test]
+    fn not_pending() {
+        let client = InMemorySecretsManager::new().with_secret(SECRET, "v1", "hunter2");
+        let result = rotation(Password::default())
+            .with_client(client)
+            .handle(event("createSecret", "v2"), LambdaContext::fake());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rotation_disabled() {
+        let client = InMemorySecretsManager::new()
+            .with_secret(SECRET, "v1", "hunter2")
+            .with_rotation_disabled(SECRET);
+        let result = rotation(Password::default())
+            .with_client(client)
+            .handle(event("createSecret", "v2"), LambdaContext::fake());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn random_password() {
+        let mut client = InMemorySecretsManager::new();
+        assert_eq!(client.get_random_password(4, "bcd").unwrap().len(), 4);
+        let letters: String = (b'a'..b'z' + 1).map(char::from).collect();
+        assert!(client.get_random_password(4, &letters).is_err());
+    }
+}
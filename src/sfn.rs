@@ -0,0 +1,359 @@
+//! Helpers for functions run as Step Functions tasks.
+//!
+//! # Error names
+//!
+//! Step Functions matches the `ErrorEquals` of `Retry` and `Catch` rules against the name of the
+//! Python exception the function raised. crowbar raises a `RuntimeError` for most errors, which is
+//! matched by `RuntimeError`, `States.TaskFailed` and `States.ALL`. Return a `TaskError` to raise
+//! an exception with a name of your choosing instead:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # fn main() {
+//! use crowbar::sfn::TaskError;
+//!
+//! // caught by "ErrorEquals": ["OrderNotFound"]
+//! lambda!(|event, _context| match event["orderId"].as_str() {
+//!     Some(id) => Ok(format!("found {}", id)),
+//!     None => Err(TaskError::new("OrderNotFound", "no orderId in input").into()),
+//! });
+//! # }
+//! ```
+//!
+//! # Callbacks
+//!
+//! A `.waitForTaskToken` task pauses until something calls `SendTaskSuccess` or
+//! `SendTaskFailure` with the task token. Pass the token in the task's `Payload` as `taskToken`,
+//! next to the rest of the input, and take a `TaskTokenEvent`:
+//!
+//! ```json
+//! "Parameters": {
+//!   "FunctionName": "approval",
+//!   "Payload": {"taskToken.$": "$$.Task.Token", "orderId.$": "$.orderId"}
+//! }
+//! ```
+//!
+//! # Scoping input and output
+//!
+//! `Scoped` gives a handler only part of the state input, and merges the handler's output back
+//! into the rest of it, like the `InputPath` and `ResultPath` of a task:
+//!
+//! ```rust
+//! # #[macro_use(lambda)] extern crate crowbar;
+//! # #[macro_use] extern crate serde_derive;
+//! # fn main() {
+//! use crowbar::{LambdaContext, LambdaResult};
+//! use crowbar::sfn::Scoped;
+//!
+//! #[derive(Deserialize)]
+//! struct Item {
+//!     price: u64,
+//!     quantity: u64,
+//! }
+//!
+//! fn total(items: Vec<Item>, _: LambdaContext) -> LambdaResult<u64> {
+//!     Ok(items.iter().map(|item| item.price * item.quantity).sum())
+//! }
+//!
+//! // {"order": {"items": [...]}} becomes {"order": {"items": [...], "total": 42}}
+//! lambda!(|event, context| {
+//!     Scoped::new("$.order.items", "$.order.total", total).handle(event, context)
+//! });
+//! # }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{self, Value};
+
+use {LambdaContext, LambdaResult};
+
+/// An error raised as a Python exception named `name`, so that Step Functions `Retry` and `Catch`
+/// rules can match it.
+///
+/// Python exception names can't contain `.`, so any dots in `name` are replaced with `_`. Names
+/// starting with `States.` are reserved by Step Functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskError {
+    name: String,
+    message: String,
+}
+
+impl TaskError {
+    pub fn new<N, M>(name: N, message: M) -> TaskError
+    where
+        N: Into<String>,
+        M: Into<String>,
+    {
+        TaskError {
+            name: name.into().replace(&['.', '\0'][..], "_"),
+            message: message.into(),
+        }
+    }
+
+    /// The error name Step Functions sees.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl ::std::error::Error for TaskError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+#[cfg(feature = "error-chain")]
+impl From<TaskError> for ::Error {
+    fn from(e: TaskError) -> ::Error {
+        ::errors::ErrorKind::TaskError(e.name, e.message).into()
+    }
+}
+
+/// The payload of a `.waitForTaskToken` task, with the token in `taskToken` and the rest of the
+/// payload as `P`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskTokenEvent<P = Value> {
+    /// The token to send back with `SendTaskSuccess`, `SendTaskFailure` or `SendTaskHeartbeat`.
+    #[serde(rename = "taskToken")]
+    pub task_token: String,
+    #[serde(flatten)]
+    pub payload: P,
+}
+
+/// A path to a value within a JSON document, in the subset of JSONPath that Step Functions allows
+/// in `InputPath` and `ResultPath`: `$`, `$.field`, `$['field']` and `$.list[0]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    /// The value at this path in `document`.
+    pub fn get<'v>(&self, document: &'v Value) -> Option<&'v Value> {
+        self.0.iter().try_fold(document, |value, segment| match *segment {
+            Segment::Field(ref field) => value.get(field),
+            Segment::Index(index) => value.get(index),
+        })
+    }
+
+    /// Puts `value` at this path in `document`, adding objects for missing fields along the way.
+    ///
+    /// This fails if the path goes through a value that isn't an object or an array, or through an
+    /// index past the end of an array.
+    pub fn set(&self, document: &mut Value, value: Value) -> Result<(), String> {
+        let mut target = document;
+        for segment in &self.0 {
+            target = match *segment {
+                Segment::Field(ref field) => {
+                    if target.is_null() {
+                        *target = Value::Object(Default::default());
+                    }
+                    match *target {
+                        Value::Object(ref mut map) => {
+                            map.entry(field.clone()).or_insert(Value::Null)
+                        }
+                        _ => return Err(format!("{} is not an object at field {}", self, field)),
+                    }
+                }
+                Segment::Index(index) => match target.get_mut(index) {
+                    Some(target) => target,
+                    None => return Err(format!("{} has no index {}", self, index)),
+                },
+            };
+        }
+        *target = value;
+        Ok(())
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<JsonPath, String> {
+        let invalid = || format!("invalid JSON path: {}", s);
+        if !s.starts_with('$') {
+            return Err(invalid());
+        }
+        let mut segments = vec![];
+        let mut rest = &s[1..];
+        while !rest.is_empty() {
+            if rest.starts_with("['") {
+                let end = rest[2..].find("']").map(|i| i + 2).ok_or_else(invalid)?;
+                segments.push(Segment::Field(rest[2..end].to_string()));
+                rest = &rest[end + 2..];
+            } else if rest.starts_with('[') {
+                let end = rest.find(']').ok_or_else(invalid)?;
+                let index = rest[1..end].parse().map_err(|_| invalid())?;
+                segments.push(Segment::Index(index));
+                rest = &rest[end + 1..];
+            } else if rest.starts_with('.') {
+                let end = rest[1..].find(&['.', '['][..]).map_or(rest.len(), |i| i + 1);
+                if end == 1 {
+                    return Err(invalid());
+                }
+                segments.push(Segment::Field(rest[1..end].to_string()));
+                rest = &rest[end..];
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(JsonPath(segments))
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.0 {
+            match *segment {
+                Segment::Field(ref field) => write!(f, "['{}']", field)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calls a handler with the part of the event at an input path, and puts its output at a result
+/// path in the event. Created with `Scoped::new`.
+pub struct Scoped<'p, F> {
+    input_path: &'p str,
+    result_path: &'p str,
+    f: F,
+}
+
+impl<'p, F> Scoped<'p, F> {
+    /// Scopes `f` to `input_path` and `result_path`. A result path of `$` replaces the whole
+    /// event with the output.
+    pub fn new(input_path: &'p str, result_path: &'p str, f: F) -> Scoped<'p, F> {
+        Scoped {
+            input_path: input_path,
+            result_path: result_path,
+            f: f,
+        }
+    }
+
+    /// Calls the handler and returns the event with its output merged in.
+    pub fn handle<'a, E, O>(self, mut event: Value, context: LambdaContext<'a>) -> LambdaResult<Value>
+    where
+        F: FnOnce(E, LambdaContext<'a>) -> LambdaResult<O>,
+        E: DeserializeOwned,
+        O: Serialize,
+    {
+        let input_path: JsonPath = self.input_path.parse()?;
+        let result_path: JsonPath = self.result_path.parse()?;
+        let input = input_path
+            .get(&event)
+            .cloned()
+            .ok_or_else(|| format!("input path {} not found in event", self.input_path))?;
+        let input = serde_json::from_value(input).map_err(|e| e.to_string())?;
+        let output = serde_json::to_value((self.f)(input, context)?).map_err(|e| e.to_string())?;
+        result_path.set(&mut event, output)?;
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpython::{ObjectProtocol, Python};
+    use LambdaContext;
+
+    #[test]
+    fn json_path() {
+        let document = json!({"order": {"items": [{"name": "crowbar"}]}});
+        let path: JsonPath = "$.order.items[0]['name']".parse().unwrap();
+        assert_eq!(path.get(&document), Some(&json!("crowbar")));
+        assert_eq!("$".parse::<JsonPath>().unwrap().get(&document), Some(&document));
+        assert_eq!("$.missing.field".parse::<JsonPath>().unwrap().get(&document), None);
+
+        let invalid = [
+            "", "order", "$.", "$..order", "$[x]", "$['order", "$[']", "$[']x", "$[", "$['",
+        ];
+        for invalid in &invalid {
+            assert!(invalid.parse::<JsonPath>().is_err(), "{} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn scoped() {
+        let event = json!({"order": {"items": [2, 3]}, "customer": "crowbar"});
+        let total = |items: Vec<u32>, _| Ok(items.iter().sum::<u32>());
+        assert_eq!(
+            Scoped::new("$.order.items", "$.results.total", total)
+                .handle(event.clone(), LambdaContext::fake())
+                .unwrap(),
+            json!({"order": {"items": [2, 3]}, "customer": "crowbar", "results": {"total": 5}})
+        );
+        assert_eq!(
+            Scoped::new("$.order.items", "$", total)
+                .handle(event.clone(), LambdaContext::fake())
+                .unwrap(),
+            json!(5)
+        );
+        assert!(
+            Scoped::new("$.customer.name", "$", |name: String, _| Ok(name))
+                .handle(event.clone(), LambdaContext::fake())
+                .is_err()
+        );
+        assert!(
+            Scoped::new("$.order.items", "$.customer.total", total)
+                .handle(event, LambdaContext::fake())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn task_token() {
+        let event: TaskTokenEvent = serde_json::from_value(json!({
+            "taskToken": "AAAAKgAAAAIAAAAAAAAAAQ",
+            "orderId": "1234"
+        })).unwrap();
+        assert_eq!(event.task_token, "AAAAKgAAAAIAAAAAAAAAAQ");
+        assert_eq!(event.payload, json!({"orderId": "1234"}));
+
+        assert_eq!(TaskError::new("Order.NotFound", "").name(), "Order_NotFound");
+    }
+
+    #[test]
+    fn task_error_exception() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let context = py
+            .eval(
+                "type('Context', (), dict(function_name='sfn', function_version='1', \
+                 invoked_function_arn='arn', memory_limit_in_mb='128', aws_request_id='id', \
+                 log_group_name='group', log_stream_name='stream', \
+                 get_remaining_time_in_millis=lambda self: 1000))()",
+                None,
+                None,
+            )
+            .unwrap();
+        let failing = |_: Value, _: LambdaContext| -> LambdaResult<Value> {
+            Err(TaskError::new("Order.NotFound", "no order 7").into())
+        };
+        let error = ::handler(py, failing, py.None(), context).unwrap_err();
+        let name = error.ptype.getattr(py, "__name__").unwrap();
+        assert_eq!(name.extract::<String>(py).unwrap(), "Order_NotFound");
+        let message = error.pvalue.unwrap().str(py).unwrap();
+        assert_eq!(message.to_string(py).unwrap(), "no order 7");
+    }
+}
@@ -0,0 +1,44 @@
+extern crate cpython;
+#[macro_use(lambda)]
+extern crate crowbar;
+
+use cpython::{ObjectProtocol, PyObject, PyResult, Python, PythonObject, ToPyObject};
+
+lambda! {
+    crate (libraw, initlibraw, PyInit_libraw) {
+        "raw" => raw |py, event, context, lambda_context| {
+            let name = context.getattr(py, "function_name")?;
+            assert_eq!(name.extract::<String>(py)?, lambda_context.function_name());
+            let response = (event, lambda_context.aws_request_id()).to_py_object(py);
+            Ok(response.into_object())
+        },
+    }
+}
+
+fn invoke(py: Python, module: &PyObject, handler: &str, event: &str) -> PyResult<String> {
+    let context = py.eval(
+        "type('Context', (), dict(function_name='raw', function_version='1', \
+         invoked_function_arn='arn', memory_limit_in_mb='128', aws_request_id='id', \
+         log_group_name='group', log_stream_name='stream', \
+         get_remaining_time_in_millis=lambda self: 1000))()",
+        None,
+        None,
+    )?;
+    let event = py.eval(event, None, None)?;
+    module
+        .getattr(py, handler)?
+        .call(py, (event, context), None)?
+        .repr(py)?
+        .to_string(py)
+        .map(|s| s.into_owned())
+}
+
+#[test]
+fn raw_handlers_skip_json() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let module = unsafe { PyObject::from_owned_ptr(py, PyInit_libraw()) };
+
+    // Neither sets nor bytes can be converted to JSON.
+    assert_eq!(invoke(py, &module, "raw", "{b'event'}").unwrap(), "({b'event'}, 'id')");
+}
@@ -0,0 +1,30 @@
+#[macro_use]
+extern crate crowbar as cb;
+
+use std::cell::Cell;
+
+#[lambdafn]
+fn owned(event: cb::Value, _context: cb::LambdaContext) -> cb::LambdaResult {
+    Ok(event)
+}
+
+#[lambdafn]
+fn borrowed<'a>(event: cb::Value, _context: &cb::LambdaContext<'a>) -> cb::LambdaResult {
+    Ok(event)
+}
+
+#[lambdafn(module = "renamed")]
+fn stateful(_event: cb::Value, seen: &Cell<usize>) -> cb::LambdaResult<usize> {
+    seen.set(seen.get() + 1);
+    Ok(seen.get())
+}
+
+module!(owned, borrowed, stateful);
+
+#[test]
+fn renamed_crate() {
+    let mut handler = stateful::handler();
+    let mut call = || handler.call(cb::Value::Null, (), |_| unreachable!());
+    assert_eq!(call().unwrap(), 1);
+    assert_eq!(call().unwrap(), 2);
+}
@@ -0,0 +1,65 @@
+extern crate cpython;
+#[macro_use(lambda)]
+extern crate crowbar;
+
+use cpython::{ObjectProtocol, PyObject, PyResult, Python};
+use crowbar::{Handler, LambdaContext, LambdaError, LambdaResult, Value};
+
+struct Counter {
+    count: u32,
+}
+
+impl Handler for Counter {
+    type Event = Value;
+    type Output = u32;
+    type Error = LambdaError;
+
+    fn call(&mut self, _: Value, _: LambdaContext) -> Result<u32, LambdaError> {
+        self.count += 1;
+        Ok(self.count)
+    }
+}
+
+lambda! {
+    crate (libstate, initlibstate, PyInit_libstate) {
+        "closure" => {
+            let mut seen = Vec::new();
+            move |event: Value, _context: LambdaContext| -> LambdaResult<Vec<Value>> {
+                seen.push(event);
+                Ok(seen.clone())
+            }
+        },
+        "counter" => impl Counter { count: 0 },
+    }
+}
+
+fn invoke(py: Python, module: &PyObject, handler: &str, event: &str) -> PyResult<String> {
+    let context = py.eval(
+        "type('Context', (), dict(function_name='state', function_version='1', \
+         invoked_function_arn='arn', memory_limit_in_mb='128', aws_request_id='id', \
+         log_group_name='group', log_stream_name='stream', \
+         get_remaining_time_in_millis=lambda self: 1000))()",
+        None,
+        None,
+    )?;
+    let event = py.eval(event, None, None)?;
+    module
+        .getattr(py, handler)?
+        .call(py, (event, context), None)?
+        .str(py)?
+        .to_string(py)
+        .map(|s| s.into_owned())
+}
+
+#[test]
+fn state_persists_across_calls() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let module = unsafe { PyObject::from_owned_ptr(py, PyInit_libstate()) };
+
+    assert_eq!(invoke(py, &module, "counter", "None").unwrap(), "1");
+    assert_eq!(invoke(py, &module, "counter", "None").unwrap(), "2");
+
+    assert_eq!(invoke(py, &module, "closure", "'a'").unwrap(), "['a']");
+    assert_eq!(invoke(py, &module, "closure", "'b'").unwrap(), "['a', 'b']");
+}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(context: &crowbar::LambdaContext, event: crowbar::Value) -> crowbar::LambdaResult {
+    println!("{}", context.function_name());
+    Ok(event)
+}
+
+fn main() {}
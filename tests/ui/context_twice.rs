@@ -0,0 +1,14 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(
+    event: crowbar::Value,
+    context: crowbar::LambdaContext,
+    again: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult {
+    println!("{} {}", context.function_name(), again.function_name());
+    Ok(event)
+}
+
+fn main() {}
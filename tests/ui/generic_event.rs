@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler<E>(event: E) -> crowbar::LambdaResult<E> {
+    Ok(event)
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(event: impl Into<crowbar::Value>) -> crowbar::LambdaResult {
+    Ok(event.into())
+}
+
+fn main() {}
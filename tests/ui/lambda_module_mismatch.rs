@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+lambda! {
+    crate kappa {
+        "handler" => |event, _context| { Ok(event) }
+    }
+}
+
+fn main() {}
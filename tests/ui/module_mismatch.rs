@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn(module = "kappa")]
+fn handler(event: crowbar::Value) -> crowbar::LambdaResult {
+    Ok(event)
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(event: crowbar::Value, context: &mut crowbar::LambdaContext) -> crowbar::LambdaResult {
+    println!("{}", context.function_name());
+    Ok(event)
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(event: crowbar::Value, count: &mut usize) -> crowbar::LambdaResult {
+    *count += 1;
+    Ok(event)
+}
+
+fn main() {}
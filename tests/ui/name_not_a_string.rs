@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn(name = handler)]
+fn handler(event: crowbar::Value) -> crowbar::LambdaResult {
+    Ok(event)
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler() -> crowbar::LambdaResult {
+    Ok("hello".into())
+}
+
+fn main() {}
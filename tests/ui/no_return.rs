@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(event: crowbar::Value, context: crowbar::LambdaContext) {
+    println!("{} {}", event, context.function_name());
+}
+
+fn main() {}
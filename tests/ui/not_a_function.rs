@@ -0,0 +1,7 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+struct Handler;
+
+fn main() {}
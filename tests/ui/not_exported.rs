@@ -0,0 +1,16 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn exported(event: crowbar::Value) -> crowbar::LambdaResult {
+    Ok(event)
+}
+
+#[lambdafn]
+fn forgotten(event: crowbar::Value) -> crowbar::LambdaResult {
+    Ok(event)
+}
+
+module!(exported);
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(event: crowbar::Value, count: usize) -> crowbar::LambdaResult {
+    println!("{}", count);
+    Ok(event)
+}
+
+fn main() {}
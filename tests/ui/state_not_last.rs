@@ -0,0 +1,15 @@
+#[macro_use]
+extern crate crowbar;
+
+#[lambdafn]
+fn handler(
+    event: crowbar::Value,
+    count: &std::cell::Cell<usize>,
+    context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult {
+    count.set(count.get() + 1);
+    println!("{}", context.function_name());
+    Ok(event)
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+lambda! {
+    crate (liblambda, initlibkappa, PyInit_liblambda) {
+        "handler" => |event, _context| { Ok(event) }
+    }
+}
+
+fn main() {}
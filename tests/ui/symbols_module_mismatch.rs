@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate crowbar;
+
+lambda! {
+    crate (libkappa, initlibkappa, PyInit_libkappa) {
+        "handler" => |event, _context| { Ok(event) }
+    }
+}
+
+fn main() {}